@@ -0,0 +1,187 @@
+//! Benchmark of the backend side of delivering terminal output to the webview
+//!
+//! Pushes 100MB of terminal-like output through the output multiplexer the app ships,
+//! `forward_output` and the frame loop, in a mock app. With a subscribed channel, frames go
+//! out as raw bytes; without one they fall back to `terminal-output-{id}` events, which
+//! serialize every session's data to a JSON number array. The webview side is replaced by a
+//! channel handler or event listener that only counts what it gets, so webview-side parsing
+//! isn't measured; it scales with the payload sizes reported here.
+//!
+//! Wall time includes the frame pacing, CPU time is what delivering the output cost.
+//!
+//! Run with `cargo run --release --example output_ipc_bench`.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tauri::ipc::{Channel, InvokeResponseBody};
+use tauri::test::MockRuntime;
+use tauri::{App, Listener, Manager};
+use tip_term::output::{self, OutputMultiplexer};
+use tokio::sync::mpsc;
+
+const TOTAL_BYTES: usize = 100 * 1024 * 1024;
+
+/// Size of one PTY read, see `READ_CHUNK_SIZE`
+const READ_CHUNK_BYTES: usize = 8192;
+
+/// Chunks the reader thread may queue, see `OUTPUT_CHANNEL_CAPACITY`
+const READ_CHANNEL_CAPACITY: usize = 64;
+
+/// Sessions sharing the output, one flooding pane and several at once
+const SESSION_COUNTS: [usize; 2] = [1, 4];
+
+#[derive(Clone, Copy)]
+enum Delivery {
+    Channel,
+    Event,
+}
+
+struct PathResult {
+    wall: Duration,
+    cpu: Duration,
+    messages: u64,
+    payload_bytes: u64,
+}
+
+/// Output of a build log with colors and some non-ASCII text, repeated to `len` bytes
+fn sample_output(len: usize) -> Vec<u8> {
+    let mut output = Vec::with_capacity(len + 256);
+    let mut line = 0u64;
+    while output.len() < len {
+        let text = match line % 4 {
+            0 => format!("\x1b[32m   Compiling\x1b[0m crate-{} v0.{}.0\r\n", line, line % 17),
+            1 => format!("\x1b[1;33mwarning\x1b[0m: unused variable `x{}` → prefix it with `_`\r\n", line),
+            2 => format!("test suite::case_{} ... \x1b[32mok\x1b[0m (Ünïcödé 日本語)\r\n", line),
+            _ => format!("{:>8} {}\r\n", line, "abcdefghijklmnopqrstuvwxyz0123456789".repeat(2)),
+        };
+        output.extend_from_slice(text.as_bytes());
+        line += 1;
+    }
+    output.truncate(len);
+    output
+}
+
+/// User and system CPU time of the process so far
+#[cfg(unix)]
+fn cpu_time() -> Duration {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) };
+    let to_duration = |time: libc::timeval| {
+        Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+    };
+    to_duration(usage.ru_utime) + to_duration(usage.ru_stime)
+}
+
+/// Not measured on Windows
+#[cfg(not(unix))]
+fn cpu_time() -> Duration {
+    Duration::ZERO
+}
+
+/// A mock app running the frame loop, with the webview replaced by counters
+fn mock_app(
+    delivery: Delivery,
+    sessions: usize,
+    messages: &Arc<AtomicU64>,
+    payload_bytes: &Arc<AtomicU64>,
+) -> App<MockRuntime> {
+    let app = tauri::test::mock_app();
+    let mux = Arc::new(OutputMultiplexer::default());
+    app.manage(mux.clone());
+    tokio::spawn(output::run_output_frames(mux.clone(), app.handle().clone()));
+
+    let count = {
+        let messages = messages.clone();
+        let payload_bytes = payload_bytes.clone();
+        move |len: usize| {
+            messages.fetch_add(1, Ordering::Relaxed);
+            payload_bytes.fetch_add(len as u64, Ordering::Relaxed);
+        }
+    };
+    match delivery {
+        Delivery::Channel => mux.subscribe(Channel::new(move |body| {
+            match body {
+                InvokeResponseBody::Raw(frame) => count(frame.len()),
+                InvokeResponseBody::Json(json) => count(json.len()),
+            }
+            Ok(())
+        })),
+        Delivery::Event => {
+            for session in 0..sessions {
+                let count = count.clone();
+                app.listen_any(format!("terminal-output-{}", session), move |event| {
+                    count(event.payload().len())
+                });
+            }
+        }
+    }
+    app
+}
+
+/// Feed `output` split across `sessions` through the multiplexer, like the PTY readers do
+async fn deliver(delivery: Delivery, sessions: usize, output: &Arc<Vec<u8>>) -> PathResult {
+    let messages = Arc::new(AtomicU64::new(0));
+    let payload_bytes = Arc::new(AtomicU64::new(0));
+    let app = mock_app(delivery, sessions, &messages, &payload_bytes);
+
+    let wall = Instant::now();
+    let cpu = cpu_time();
+    let mut tasks = Vec::new();
+    for session in 0..sessions {
+        let (tx, mut rx) = mpsc::channel(READ_CHANNEL_CAPACITY);
+        let output = output.clone();
+        tasks.push(tokio::spawn(async move {
+            let share = output.len() / sessions;
+            let data = &output[session * share..(session + 1) * share];
+            for chunk in data.chunks(READ_CHUNK_BYTES) {
+                if tx.send(chunk.to_vec()).await.is_err() {
+                    break;
+                }
+            }
+        }));
+        let app = app.handle().clone();
+        tasks.push(tokio::spawn(async move {
+            let id = session.to_string();
+            // Returns once everything it read has been sent
+            output::forward_output(&id, &mut rx, None, None, &app).await;
+        }));
+    }
+    for task in tasks {
+        task.await.expect("Delivery task failed");
+    }
+
+    PathResult {
+        wall: wall.elapsed(),
+        cpu: cpu_time() - cpu,
+        messages: messages.load(Ordering::Relaxed),
+        payload_bytes: payload_bytes.load(Ordering::Relaxed),
+    }
+}
+
+fn report(name: &str, result: &PathResult) {
+    let mb = TOTAL_BYTES as f64 / (1024.0 * 1024.0);
+    println!(
+        "  {:<8} {:>8.1} ms wall {:>8.1} ms cpu {:>9.1} MB/s {:>6} messages {:>8.1} MB sent ({:.2}x)",
+        name,
+        result.wall.as_secs_f64() * 1000.0,
+        result.cpu.as_secs_f64() * 1000.0,
+        mb / result.wall.as_secs_f64(),
+        result.messages,
+        result.payload_bytes as f64 / (1024.0 * 1024.0),
+        result.payload_bytes as f64 / TOTAL_BYTES as f64,
+    );
+}
+
+#[tokio::main]
+async fn main() {
+    let output = Arc::new(sample_output(TOTAL_BYTES));
+    println!("{} MB of terminal output", TOTAL_BYTES / (1024 * 1024));
+
+    for sessions in SESSION_COUNTS {
+        println!("{} session(s):", sessions);
+        report("channel", &deliver(Delivery::Channel, sessions, &output).await);
+        report("event", &deliver(Delivery::Event, sessions, &output).await);
+    }
+}
@@ -0,0 +1,50 @@
+# TipTerm shell integration for bash, loaded with --init-file
+# Reports command boundaries with OSC 133 and the working directory with OSC 7.
+
+# --init-file replaces the usual startup files, load them like bash would
+if [[ -n "$TIPTERM_BASH_LOGIN" ]]; then
+  unset TIPTERM_BASH_LOGIN
+  [[ -f /etc/profile ]] && . /etc/profile
+  if [[ -f ~/.bash_profile ]]; then
+    . ~/.bash_profile
+  elif [[ -f ~/.bash_login ]]; then
+    . ~/.bash_login
+  elif [[ -f ~/.profile ]]; then
+    . ~/.profile
+  fi
+else
+  [[ -f /etc/bash.bashrc ]] && . /etc/bash.bashrc
+  [[ -f ~/.bashrc ]] && . ~/.bashrc
+fi
+
+if [[ $- == *i* && -z "$TIPTERM_SHELL_INTEGRATION" ]]; then
+  TIPTERM_SHELL_INTEGRATION=1
+
+  __tipterm_urlencode() {
+    local LC_ALL=C input="$1" out="" c i
+    for (( i = 0; i < ${#input}; i++ )); do
+      c="${input:i:1}"
+      case "$c" in
+        [a-zA-Z0-9/._~-]) out+="$c" ;;
+        *) printf -v c '%%%02X' "'$c"; out+="$c" ;;
+      esac
+    done
+    printf '%s' "$out"
+  }
+
+  # D is ignored by the terminal when no command was started, so it can be sent unconditionally
+  __tipterm_prompt_command() {
+    local exit_code=$?
+    printf '\e]133;D;%s\a' "$exit_code"
+    printf '\e]7;file://%s%s\a' "$HOSTNAME" "$(__tipterm_urlencode "$PWD")"
+    printf '\e]133;A\a'
+    # The command text is read from the screen between B and C
+    if [[ "$PS1" != *'133;B'* ]]; then
+      PS1="$PS1"'\[\e]133;B\a\]'
+    fi
+    return $exit_code
+  }
+
+  PROMPT_COMMAND="__tipterm_prompt_command${PROMPT_COMMAND:+; $PROMPT_COMMAND}"
+  PS0="${PS0}"'\e]133;C\a'
+fi
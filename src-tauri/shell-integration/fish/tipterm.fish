@@ -0,0 +1,32 @@
+# TipTerm shell integration for fish, loaded with --init-command
+# Reports command boundaries with OSC 133 and the working directory with OSC 7.
+
+if status is-interactive; and not set -q TIPTERM_SHELL_INTEGRATION
+    set -g TIPTERM_SHELL_INTEGRATION 1
+
+    # Escape each path component, `string escape --style=url` would escape the slashes too
+    function __tipterm_urlencode
+        string join / -- (string escape --style=url -- (string split / -- $argv[1]))
+    end
+
+    # Runs after config.fish, so the prompt marks come after anything it prints
+    function __tipterm_prompt --on-event fish_prompt
+        printf '\e]7;file://%s%s\a' $hostname (__tipterm_urlencode $PWD)
+        printf '\e]133;A\a'
+    end
+
+    function __tipterm_preexec --on-event fish_preexec
+        printf '\e]133;C;cmdline_url=%s\a' (string escape --style=url -- $argv[1])
+    end
+
+    function __tipterm_postexec --on-event fish_postexec
+        printf '\e]133;D;%s\a' $status
+    end
+
+    # Mark where the command line starts, after the user's prompt
+    functions -c fish_prompt __tipterm_user_prompt
+    function fish_prompt
+        __tipterm_user_prompt
+        printf '\e]133;B\a'
+    end
+end
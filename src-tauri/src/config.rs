@@ -1,11 +1,15 @@
 use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Deserialize;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use tauri::{AppHandle, Emitter, State};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tip_term::TriggerConfig;
 
 pub struct ConfigWatchState {
     pub watcher: Option<RecommendedWatcher>,
     pub watched_path: Option<PathBuf>,
+    /// Parts of the terminal config the backend acts on, reloaded when the file changes
+    pub backend: BackendConfig,
 }
 
 impl ConfigWatchState {
@@ -13,10 +17,62 @@ impl ConfigWatchState {
         Self {
             watcher: None,
             watched_path: None,
+            backend: BackendConfig::default(),
         }
     }
 }
 
+/// Terminal config settings used by the backend, everything else belongs to the frontend
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BackendConfig {
+    pub command_notifications: CommandNotificationConfig,
+    /// Regexes matched against session output, see `triggers.rs`
+    pub triggers: Vec<TriggerConfig>,
+}
+
+/// Desktop notification when a long-running command finishes in an unfocused session
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CommandNotificationConfig {
+    pub enabled: bool,
+    /// Commands that finish sooner than this don't notify
+    pub min_duration_ms: u64,
+}
+
+impl Default for CommandNotificationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_duration_ms: 10_000,
+        }
+    }
+}
+
+/// Read the backend settings from the terminal config file, falling back to defaults
+fn load_backend_config(path: &Path) -> BackendConfig {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(_) => return BackendConfig::default(),
+    };
+    let mut config: BackendConfig = match serde_json::from_str(&content) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Failed to parse {}: {}", path.display(), e);
+            return BackendConfig::default();
+        }
+    };
+    // A broken pattern shouldn't keep the other triggers from working
+    config.triggers.retain(|trigger| match trigger.compile() {
+        Ok(_) => true,
+        Err(e) => {
+            eprintln!("Ignoring trigger in {}: {}", path.display(), e);
+            false
+        }
+    });
+    config
+}
+
 fn expand_tilde(path: &str) -> PathBuf {
     if path.starts_with('~') {
         if let Some(home) = std::env::var_os("HOME") {
@@ -49,6 +105,10 @@ pub async fn start_terminal_config_watcher(
         if let Ok(event) = res {
             let matches_target = event.paths.iter().any(|p| p == &path_clone);
             if matches_target {
+                let backend = load_backend_config(&path_clone);
+                let state = app_handle.state::<Arc<Mutex<ConfigWatchState>>>();
+                state.lock().unwrap().backend = backend;
+                crate::triggers::apply_triggers(&app_handle);
                 let _ = app_handle.emit("terminal-config-changed", path_clone.to_string_lossy().to_string());
             }
         }
@@ -59,9 +119,13 @@ pub async fn start_terminal_config_watcher(
         .watch(&watch_dir, RecursiveMode::NonRecursive)
         .map_err(|e| format!("Failed to watch config directory: {}", e))?;
 
-    let mut state = state.lock().unwrap();
-    state.watcher = Some(watcher);
-    state.watched_path = Some(path);
+    {
+        let mut state = state.lock().unwrap();
+        state.backend = load_backend_config(&path);
+        state.watcher = Some(watcher);
+        state.watched_path = Some(path);
+    }
+    crate::triggers::apply_triggers(&app);
 
     Ok(())
 }
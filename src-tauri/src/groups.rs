@@ -0,0 +1,166 @@
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use uuid::Uuid;
+
+use crate::TerminalState;
+
+/// Sessions that receive the same input through `broadcast_to_group`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionGroup {
+    pub id: String,
+    pub name: String,
+    pub members: Vec<String>,
+}
+
+/// A member `broadcast_to_group` couldn't write to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BroadcastFailure {
+    pub session_id: String,
+    pub error: String,
+}
+
+/// Result of `broadcast_to_group`, every member is either written or failed
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BroadcastResult {
+    pub written: Vec<String>,
+    pub failed: Vec<BroadcastFailure>,
+}
+
+fn session_exists(state: &TerminalState, id: &str) -> bool {
+    state.writers.contains_key(id) || state.daemon_sessions.contains(id)
+}
+
+fn group_mut<'a>(
+    state: &'a mut TerminalState,
+    group_id: &str,
+) -> Result<&'a mut SessionGroup, String> {
+    state
+        .groups
+        .get_mut(group_id)
+        .ok_or_else(|| "Group not found".to_string())
+}
+
+/// Create a group, optionally with initial members
+#[tauri::command]
+pub async fn create_session_group(
+    name: String,
+    members: Option<Vec<String>>,
+    state: State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<SessionGroup, String> {
+    let mut state = state.lock().unwrap();
+    let members = members.unwrap_or_default();
+    if let Some(missing) = members.iter().find(|id| !session_exists(&state, id)) {
+        return Err(format!("Session not found: {}", missing));
+    }
+
+    let mut group = SessionGroup {
+        id: Uuid::new_v4().to_string(),
+        name,
+        members: Vec::new(),
+    };
+    for id in members {
+        if !group.members.contains(&id) {
+            group.members.push(id);
+        }
+    }
+    state.groups.insert(group.id.clone(), group.clone());
+    Ok(group)
+}
+
+/// Delete a group, its sessions keep running
+#[tauri::command]
+pub async fn delete_session_group(
+    group_id: String,
+    state: State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<(), String> {
+    state
+        .lock()
+        .unwrap()
+        .groups
+        .remove(&group_id)
+        .map(|_| ())
+        .ok_or_else(|| "Group not found".to_string())
+}
+
+#[tauri::command]
+pub async fn add_to_session_group(
+    group_id: String,
+    session_id: String,
+    state: State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<SessionGroup, String> {
+    let mut state = state.lock().unwrap();
+    if !session_exists(&state, &session_id) {
+        return Err("Session not found".to_string());
+    }
+    let group = group_mut(&mut state, &group_id)?;
+    if !group.members.contains(&session_id) {
+        group.members.push(session_id);
+    }
+    Ok(group.clone())
+}
+
+#[tauri::command]
+pub async fn remove_from_session_group(
+    group_id: String,
+    session_id: String,
+    state: State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<SessionGroup, String> {
+    let mut state = state.lock().unwrap();
+    let group = group_mut(&mut state, &group_id)?;
+    group.members.retain(|member| *member != session_id);
+    Ok(group.clone())
+}
+
+#[tauri::command]
+pub async fn list_session_groups(
+    state: State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<Vec<SessionGroup>, String> {
+    let state = state.lock().unwrap();
+    let mut groups: Vec<SessionGroup> = state.groups.values().cloned().collect();
+    groups.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(groups)
+}
+
+/// Write the same text to every session in a group
+/// Members are written concurrently, a failing member doesn't stop the others.
+#[tauri::command]
+pub async fn broadcast_to_group(
+    group_id: String,
+    data: String,
+    state: State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<BroadcastResult, String> {
+    let members = {
+        let state = state.lock().unwrap();
+        state
+            .groups
+            .get(&group_id)
+            .ok_or_else(|| "Group not found".to_string())?
+            .members
+            .clone()
+    };
+
+    let mut writes = tokio::task::JoinSet::new();
+    for id in members {
+        let state = state.inner().clone();
+        let data = data.clone();
+        writes.spawn(async move {
+            let result = crate::write_text(&state, id.clone(), data).await;
+            (id, result)
+        });
+    }
+
+    let mut result = BroadcastResult::default();
+    while let Some(joined) = writes.join_next().await {
+        match joined {
+            Ok((id, Ok(()))) => result.written.push(id),
+            Ok((id, Err(error))) => result.failed.push(BroadcastFailure { session_id: id, error }),
+            Err(e) => eprintln!("Broadcast write task failed: {}", e),
+        }
+    }
+    Ok(result)
+}
@@ -1,5 +1,8 @@
 // Re-export terminal module for library use
 pub mod terminal;
 
-pub use terminal::TerminalSession;
-pub use terminal::vte_parser::{PtyWriter, ProcessInfo, get_process_info_by_pid};
+// Output delivery to the webview, here so tests and benchmarks can drive it
+pub mod output;
+
+pub use terminal::{TerminalSession, SessionKind, SessionSpec, ShellKind, ScrollbackSnapshot, ScreenSnapshot, TerminalScreen, FlowControl, SessionMetrics, SessionMetricsSnapshot, PasteResult, RecordingInfo, LogFormat, LogOptions, ShellEvent, ShellEventReceiver, TriggerAction, TriggerConfig, TriggerEvent, TriggerEventReceiver, PtyOutputReceiver, PtyWriter, SessionExitStatus, SessionCloseResult, SessionSignal};
+pub use terminal::vte_parser::{ProcessInfo, ProcessNode, ProcessChangeReceiver, get_process_info_by_pid, lock_session, watch_foreground_process};
@@ -4,26 +4,45 @@
 mod filesystem;
 mod git;
 mod config;
+mod groups;
+mod metrics;
+mod notifications;
+mod persistence;
+mod playback;
+mod session_daemon;
+mod triggers;
 
-use std::collections::HashMap;
-use std::io::Write;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+use std::time::Duration;
 use tauri::{AppHandle, Emitter, Manager};
 use uuid::Uuid;
 
 // Import types from lib (tip_term library)
-use tip_term::{TerminalSession, ProcessInfo};
+use tip_term::terminal::daemon::{self, DaemonClient, DaemonReply, DaemonRequest};
+use tip_term::terminal::{paste, write_pty};
+use tip_term::{lock_session, PtyWriter, TerminalSession, SessionSpec, FlowControl, PasteResult, ScrollbackSnapshot, ScreenSnapshot, RecordingInfo, LogFormat, LogOptions, ShellEvent, ShellEventReceiver, ProcessInfo, ProcessNode, ProcessChangeReceiver, PtyOutputReceiver, SessionExitStatus, SessionCloseResult, SessionSignal};
 use config::ConfigWatchState;
-
-/// Type alias for the writer
-type PtyWriter = Arc<Mutex<Box<dyn Write + Send>>>;
+use groups::SessionGroup;
+use notifications::FocusState;
+use tip_term::output::{self, OutputMultiplexer};
+use persistence::RestoreState;
 
 /// Global state for terminal sessions
 pub struct TerminalState {
     pub sessions: HashMap<String, Arc<Mutex<TerminalSession>>>,
     pub writers: HashMap<String, PtyWriter>,
     pub session_pids: HashMap<String, u32>,
+    /// Connection to the session daemon, when enabled
+    pub daemon: Option<Arc<DaemonClient>>,
+    /// Sessions owned by the daemon, commands on these are proxied to it
+    pub daemon_sessions: HashSet<String>,
+    /// Read-only sessions replaying a recording
+    pub playbacks: HashMap<String, playback::Playback>,
+    /// Session groups for `broadcast_to_group`, by group ID
+    pub groups: HashMap<String, SessionGroup>,
+    /// Task emitting `session-metrics`, see `set_session_metrics_interval`
+    pub metrics_task: Option<tokio::task::AbortHandle>,
 }
 
 impl TerminalState {
@@ -32,21 +51,194 @@ impl TerminalState {
             sessions: HashMap::new(),
             writers: HashMap::new(),
             session_pids: HashMap::new(),
+            daemon: None,
+            daemon_sessions: HashSet::new(),
+            playbacks: HashMap::new(),
+            groups: HashMap::new(),
+            metrics_task: None,
+        }
+    }
+
+    /// Daemon client to proxy through if `id` is a daemon session
+    pub fn daemon_client(&self, id: &str) -> Option<Arc<DaemonClient>> {
+        if self.daemon_sessions.contains(id) {
+            self.daemon.clone()
+        } else {
+            None
+        }
+    }
+
+    /// Drop a session that went away from the groups it was in
+    pub fn leave_groups(&mut self, id: &str) {
+        for group in self.groups.values_mut() {
+            group.members.retain(|member| member != id);
         }
     }
 }
 
-/// Create a new terminal session
-#[tauri::command]
-async fn create_session(
-    shell: String,
+/// Event payload tagged with the session it came from
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionEvent<'a, T: serde::Serialize> {
+    session_id: &'a str,
+    #[serde(flatten)]
+    event: T,
+}
+
+/// Emit a shell integration event as `terminal-command-started`, `terminal-command-finished`
+/// or `terminal-cwd-changed`, tagged with the session id
+pub(crate) fn emit_shell_event(session_id: &str, event: ShellEvent, app: &AppHandle) {
+    let result = match event {
+        ShellEvent::CommandStarted(event) => {
+            app.emit("terminal-command-started", SessionEvent { session_id, event })
+        }
+        ShellEvent::CommandFinished(event) => {
+            notifications::notify_command_finished(session_id, &event, app);
+            app.emit("terminal-command-finished", SessionEvent { session_id, event })
+        }
+        ShellEvent::CwdChanged { cwd } => app.emit(
+            "terminal-cwd-changed",
+            SessionEvent { session_id, event: serde_json::json!({ "cwd": cwd }) },
+        ),
+    };
+    if let Err(e) = result {
+        eprintln!("Failed to emit shell event: {}", e);
+    }
+}
+
+/// Forward a local session's shell integration events until its reader thread stops
+async fn forward_shell_events(session_id: String, mut events: ShellEventReceiver, app: AppHandle) {
+    while let Some(event) = events.recv().await {
+        emit_shell_event(&session_id, event, &app);
+    }
+}
+
+/// Emit `session-process-changed` when a session's foreground process or its cwd changes
+pub(crate) fn emit_process_changed(session_id: &str, info: ProcessInfo, app: &AppHandle) {
+    if let Err(e) = app.emit("session-process-changed", SessionEvent { session_id, event: info }) {
+        eprintln!("Failed to emit process change: {}", e);
+    }
+}
+
+/// Emit `terminal-recording-finished-{id}` for a recording that ended with its session
+pub(crate) fn emit_recording_finished(session_id: &str, info: RecordingInfo, app: &AppHandle) {
+    if let Err(e) = app.emit(&format!("terminal-recording-finished-{}", session_id), info) {
+        eprintln!("Failed to emit recording finished: {}", e);
+    }
+}
+
+/// Forward a local session's foreground process changes until the session is dropped
+async fn forward_process_changes(
+    session_id: String,
+    mut changes: ProcessChangeReceiver,
     app: AppHandle,
-    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+) {
+    while let Some(info) = changes.recv().await {
+        emit_process_changed(&session_id, info, &app);
+    }
+}
+
+/// Run a local session's output loop
+/// Once the PTY closes, the session is reaped and a `terminal-exit-{id}` event is emitted.
+async fn run_output_loop(
+    session_id: String,
+    session: Arc<Mutex<TerminalSession>>,
+    mut output_rx: PtyOutputReceiver,
+    app: AppHandle,
+) {
+    eprintln!("Terminal output loop started for session {}", session_id);
+    let (flow, metrics) = {
+        let session = lock_session(&session);
+        (session.output_flow(), session.metrics())
+    };
+    let forwarding =
+        output::forward_output(&session_id, &mut output_rx, Some(flow.clone()), Some(metrics), &app);
+    tokio::pin!(forwarding);
+    tokio::select! {
+        _ = &mut forwarding => {}
+        _ = open_flow_on_exit(&session, &flow) => forwarding.await,
+    }
+
+    let status = wait_for_exit(&session).await;
+    eprintln!("Terminal session {} ended: {:?}", session_id, status);
+
+    // Nothing more will be recorded, the cast is complete without a `stop_recording`
+    let recording = lock_session(&session).stop_recording();
+    match recording {
+        Ok(Some(info)) => emit_recording_finished(&session_id, info, &app),
+        Ok(None) => {}
+        Err(e) => eprintln!("Failed to finish recording of session {}: {}", session_id, e),
+    }
+
+    {
+        let state = app.state::<Arc<Mutex<TerminalState>>>();
+        let mut state = state.lock().unwrap();
+        // close_session may already have removed it, or the id may have been reused
+        let is_current = state
+            .sessions
+            .get(&session_id)
+            .is_some_and(|current| Arc::ptr_eq(current, &session));
+        if is_current {
+            state.sessions.remove(&session_id);
+            state.writers.remove(&session_id);
+            state.session_pids.remove(&session_id);
+            state.leave_groups(&session_id);
+        }
+    }
+
+    if let Err(e) = app.emit(&format!("terminal-exit-{}", session_id), status) {
+        eprintln!("Failed to emit terminal exit: {}", e);
+    }
+}
+
+/// Open a session's output window for good once its child has exited while the window is closed
+/// The PTY only reaches EOF once the output still buffered has been read, which would wait
+/// for acks that may never come, e.g. when the webview is gone.
+async fn open_flow_on_exit(session: &Arc<Mutex<TerminalSession>>, flow: &FlowControl) {
+    const EXIT_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+    loop {
+        tokio::time::sleep(EXIT_CHECK_INTERVAL).await;
+        if !flow.is_open() && lock_session(session).try_wait().is_some() {
+            flow.disable();
+            return;
+        }
+    }
+}
+
+/// Wait for a session's child to exit after its PTY has closed
+/// The PTY usually closes when the child exits, so this rarely polls more than once or twice.
+async fn wait_for_exit(session: &Arc<Mutex<TerminalSession>>) -> SessionExitStatus {
+    const EXIT_POLL_INTERVAL_MS: u64 = 10;
+    loop {
+        let status = lock_session(session).try_wait();
+        if let Some(status) = status {
+            return status;
+        }
+        tokio::time::sleep(Duration::from_millis(EXIT_POLL_INTERVAL_MS)).await;
+    }
+}
+
+/// Spawn a session, register it in `TerminalState` and start forwarding its output
+/// `history` is seeded into the scrollback ahead of the live output.
+pub(crate) fn spawn_session(
+    shell: String,
+    spec: SessionSpec,
+    history: &[u8],
+    title: Option<String>,
+    app: &AppHandle,
+    state: &Mutex<TerminalState>,
 ) -> Result<String, String> {
     let session_id = Uuid::new_v4().to_string();
 
-    let (session, writer, child_pid) = TerminalSession::new(80, 24, shell)
+    let (mut session, writer, child_pid) = TerminalSession::new(shell, &spec)
         .map_err(|e| format!("Failed to create terminal: {}", e))?;
+    session.seed_scrollback(history);
+    session.set_title(title);
+    let output_rx = session
+        .start_reader()
+        .map_err(|e| format!("Failed to start terminal reader: {}", e))?;
+    let shell_events = session.take_shell_events();
+    let trigger_events = session.take_trigger_events();
 
     let mut state = state.lock().unwrap();
     let session_arc = Arc::new(Mutex::new(session));
@@ -54,84 +246,254 @@ async fn create_session(
     state.writers.insert(session_id.clone(), writer);
     state.session_pids.insert(session_id.clone(), child_pid);
 
-    let session_id_clone = session_id.clone();
-    let app_clone = app.clone();
-    tokio::spawn(async move {
-        eprintln!("Terminal output loop started for session {}", session_id_clone);
-        const BATCH_DURATION_MS: u64 = 4;
-        const BATCH_MAX_SIZE: usize = 64 * 1024;
-        let mut buffer: Vec<u8> = Vec::with_capacity(BATCH_MAX_SIZE);
-        let mut last_flush = Instant::now();
-        loop {
-            tokio::time::sleep(Duration::from_millis(2)).await;
-
-            let output = {
-                let mut session = session_arc.lock().unwrap();
-                session.read_output().unwrap_or(None)
-            };
+    let process_changes = tip_term::watch_foreground_process(&session_arc);
+    tokio::spawn(forward_process_changes(session_id.clone(), process_changes, app.clone()));
+    tokio::spawn(run_output_loop(session_id.clone(), session_arc, output_rx, app.clone()));
+    if let Some(events) = shell_events {
+        tokio::spawn(forward_shell_events(session_id.clone(), events, app.clone()));
+    }
+    if let Some(events) = trigger_events {
+        tokio::spawn(triggers::forward_trigger_events(session_id.clone(), events, app.clone()));
+    }
 
-            if let Some(data) = output {
-                if buffer.is_empty() && data.len() <= 4096 {
-                    if let Err(e) = app_clone.emit(&format!("terminal-output-{}", session_id_clone), data) {
-                        eprintln!("Failed to emit terminal output: {}", e);
-                        break;
-                    }
-                    last_flush = Instant::now();
-                } else {
-                    buffer.extend_from_slice(&data);
-                }
-            }
+    Ok(session_id)
+}
 
-            let should_flush = !buffer.is_empty()
-                && (buffer.len() >= BATCH_MAX_SIZE
-                    || last_flush.elapsed().as_millis() >= BATCH_DURATION_MS as u128);
+/// Create a new terminal session
+/// `spec` controls the initial size, cwd, environment and arguments, defaults to 80x24 login shell
+/// An empty `shell` starts the user's shell, for command sessions `shell` is the program to run.
+/// When the session daemon is connected, the session is created inside the daemon.
+#[tauri::command]
+async fn create_session(
+    shell: String,
+    spec: Option<SessionSpec>,
+    app: AppHandle,
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<String, String> {
+    let mut spec = spec.unwrap_or_default();
+    spec.triggers = triggers::configured_triggers(&app);
+    let daemon = state.lock().unwrap().daemon.clone();
+    if let Some(client) = daemon {
+        return session_daemon::create_remote_session(&client, shell, spec, &state).await;
+    }
 
-            if should_flush {
-                let payload = buffer.split_off(0);
-                last_flush = Instant::now();
-                if let Err(e) = app_clone.emit(&format!("terminal-output-{}", session_id_clone), payload) {
-                    eprintln!("Failed to emit terminal output: {}", e);
-                    break;
-                }
-            }
+    spawn_session(shell, spec, &[], None, &app, &state)
+}
 
-            {
-                let mut session = session_arc.lock().unwrap();
-                if !session.is_alive() {
-                    eprintln!("Terminal session {} ended", session_id_clone);
-                    break;
-                }
-            }
+/// Set the title saved with a session, e.g. the user's custom name or the OSC title
+#[tauri::command]
+async fn set_session_title(
+    id: String,
+    title: Option<String>,
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<(), String> {
+    let session = {
+        let state = state.lock().unwrap();
+        if state.daemon_sessions.contains(&id) {
+            // Daemon sessions outlive the app, they are never persisted
+            return Ok(());
         }
-    });
+        state
+            .sessions
+            .get(&id)
+            .ok_or_else(|| "Session not found".to_string())?
+            .clone()
+    };
 
-    Ok(session_id)
+    lock_session(&session).set_title(title);
+    Ok(())
 }
 
-/// Write data to a terminal session
+/// Writer of a local session, playbacks can't be written to
+fn local_writer(state: &TerminalState, id: &str) -> Result<PtyWriter, String> {
+    if state.playbacks.contains_key(id) {
+        return Err("Session is read-only".to_string());
+    }
+    state
+        .writers
+        .get(id)
+        .cloned()
+        .ok_or_else(|| "Session not found".to_string())
+}
+
+/// Write text to a terminal session, encoded in the session's input encoding
 #[tauri::command]
 async fn write_to_session(
     id: String,
     data: String,
     state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
 ) -> Result<(), String> {
-    let writer = {
+    write_text(&state, id, data).await
+}
+
+pub(crate) async fn write_text(
+    state: &Mutex<TerminalState>,
+    id: String,
+    data: String,
+) -> Result<(), String> {
+    let client = state.lock().unwrap().daemon_client(&id);
+    if let Some(client) = client {
+        let request = DaemonRequest::WriteText { id, text: data };
+        return client.request(request).await.map(|_| ());
+    }
+
+    let (writer, session) = {
+        let state = state.lock().unwrap();
+        (local_writer(&state, &id)?, state.sessions.get(&id).cloned())
+    };
+    let bytes = match &session {
+        Some(session) => {
+            let session = lock_session(session);
+            session.record_input(data.as_bytes());
+            session.encode_input(&data).into_owned()
+        }
+        None => data.into_bytes(),
+    };
+    write_pty(&writer, bytes).await
+}
+
+/// Write raw bytes to a terminal session, e.g. control bytes or pre-encoded text
+#[tauri::command]
+async fn write_bytes_to_session(
+    id: String,
+    data: Vec<u8>,
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<(), String> {
+    let client = state.lock().unwrap().daemon_client(&id);
+    if let Some(client) = client {
+        return client.request(DaemonRequest::Write { id, data }).await.map(|_| ());
+    }
+
+    let (writer, session) = {
+        let state = state.lock().unwrap();
+        (local_writer(&state, &id)?, state.sessions.get(&id).cloned())
+    };
+    if let Some(session) = session {
+        lock_session(&session).record_input(&data);
+    }
+    write_pty(&writer, data).await
+}
+
+/// Change the encoding used for text written with `write_to_session`
+/// Takes a WHATWG label such as "utf-8", "shift_jis" or "windows-1252".
+#[tauri::command]
+async fn set_session_input_encoding(
+    id: String,
+    encoding: String,
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<(), String> {
+    let client = state.lock().unwrap().daemon_client(&id);
+    if let Some(client) = client {
+        return client
+            .request(DaemonRequest::SetInputEncoding { id, encoding })
+            .await
+            .map(|_| ());
+    }
+
+    let session = {
         let state = state.lock().unwrap();
         state
-            .writers
+            .sessions
             .get(&id)
             .ok_or_else(|| "Session not found".to_string())?
             .clone()
     };
+    let result = lock_session(&session).set_input_encoding(&encoding);
+    result.map_err(|e| e.to_string())
+}
 
-    let mut writer = writer.lock().unwrap();
-    writer
-        .write_all(data.as_bytes())
-        .map_err(|e| format!("Write failed: {}", e))?;
-    writer.flush().map_err(|e| format!("Flush failed: {}", e))?;
+/// Output flow of a local session, `None` for playbacks which aren't flow controlled
+fn local_output_flow(state: &TerminalState, id: &str) -> Result<Option<Arc<FlowControl>>, String> {
+    if state.playbacks.contains_key(id) {
+        return Ok(None);
+    }
+    let session = state
+        .sessions
+        .get(id)
+        .ok_or_else(|| "Session not found".to_string())?;
+    let flow = lock_session(session).output_flow();
+    Ok(Some(flow))
+}
+
+/// Acknowledge `bytes` of `terminal-output-{id}` data the frontend has rendered
+/// The backend stops reading a session's PTY once it is 1MB ahead of the acks.
+#[tauri::command]
+async fn ack_session_output(
+    id: String,
+    bytes: u64,
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<(), String> {
+    let client = state.lock().unwrap().daemon_client(&id);
+    if let Some(client) = client {
+        return client
+            .request(DaemonRequest::AckOutput { id, bytes })
+            .await
+            .map(|_| ());
+    }
+
+    if let Some(flow) = local_output_flow(&state.lock().unwrap(), &id)? {
+        flow.ack(bytes);
+    }
     Ok(())
 }
 
+/// Forget a session's unacked output, for a frontend that starts listening after a reload
+#[tauri::command]
+async fn reset_session_output_window(
+    id: String,
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<(), String> {
+    let client = state.lock().unwrap().daemon_client(&id);
+    if let Some(client) = client {
+        return client
+            .request(DaemonRequest::ResetOutputWindow { id })
+            .await
+            .map(|_| ());
+    }
+
+    if let Some(flow) = local_output_flow(&state.lock().unwrap(), &id)? {
+        flow.reset();
+    }
+    Ok(())
+}
+
+/// Paste text into a terminal session
+/// The text is wrapped in bracketed-paste markers when the program enabled mode 2004 and
+/// large pastes are written in chunks. Without bracketed paste, multi-line and `sudo`
+/// pastes return `needsConfirmation` unless `confirmed` is set.
+#[tauri::command]
+async fn paste_to_session(
+    id: String,
+    text: String,
+    confirmed: Option<bool>,
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<PasteResult, String> {
+    let confirmed = confirmed.unwrap_or(false);
+
+    let client = state.lock().unwrap().daemon_client(&id);
+    if let Some(client) = client {
+        return match client.request(DaemonRequest::Paste { id, text, confirmed }).await? {
+            DaemonReply::Pasted { result } => Ok(result),
+            reply => Err(format!("Unexpected reply from session daemon: {:?}", reply)),
+        };
+    }
+
+    let (writer, session) = {
+        let state = state.lock().unwrap();
+        let writer = local_writer(&state, &id)?;
+        let session = state
+            .sessions
+            .get(&id)
+            .ok_or_else(|| "Session not found".to_string())?
+            .clone();
+        (writer, session)
+    };
+
+    paste::paste(&session, &writer, &text, confirmed)
+        .await
+        .map_err(|e| format!("Paste failed: {}", e))
+}
+
 /// Resize a terminal session
 #[tauri::command]
 async fn resize_terminal(
@@ -140,8 +502,18 @@ async fn resize_terminal(
     rows: usize,
     state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
 ) -> Result<(), String> {
+    let client = state.lock().unwrap().daemon_client(&id);
+    if let Some(client) = client {
+        let request = DaemonRequest::Resize { id, cols: cols as u16, rows: rows as u16 };
+        return client.request(request).await.map(|_| ());
+    }
+
     let session = {
         let state = state.lock().unwrap();
+        if state.playbacks.contains_key(&id) {
+            // Playback keeps the recorded size
+            return Ok(());
+        }
         state
             .sessions
             .get(&id)
@@ -149,42 +521,374 @@ async fn resize_terminal(
             .clone()
     };
 
-    let mut session = session.lock().unwrap();
+    let mut session = lock_session(&session);
     session.resize(cols, rows).map_err(|e| format!("Resize failed: {}", e))?;
     Ok(())
 }
 
+/// Default time a session gets to exit after each signal on close
+const DEFAULT_CLOSE_GRACE_PERIOD_MS: u64 = 1000;
+
 /// Close a terminal session
+/// Hangs up the PTY's process groups, escalating to SIGTERM/SIGKILL after the grace
+/// period, and reports whether the child exited cleanly.
+/// Returns None if the session is already gone, e.g. because its process exited.
 #[tauri::command]
 async fn close_session(
     id: String,
+    grace_period_ms: Option<u64>,
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<Option<SessionCloseResult>, String> {
+    let grace_period_ms = grace_period_ms.unwrap_or(DEFAULT_CLOSE_GRACE_PERIOD_MS);
+    let client = {
+        let mut state = state.lock().unwrap();
+        if let Some(playback) = state.playbacks.remove(&id) {
+            playback.abort();
+            return Ok(None);
+        }
+        let client = state.daemon_client(&id);
+        if client.is_some() {
+            state.daemon_sessions.remove(&id);
+            state.session_pids.remove(&id);
+            state.leave_groups(&id);
+        }
+        client
+    };
+    if let Some(client) = client {
+        return match client.request(DaemonRequest::Close { id, grace_period_ms }).await? {
+            DaemonReply::Closed { result } => Ok(Some(result)),
+            reply => Err(format!("Unexpected reply from session daemon: {:?}", reply)),
+        };
+    }
+
+    let session = {
+        let mut state = state.lock().unwrap();
+        state.writers.remove(&id);
+        state.session_pids.remove(&id);
+        state.leave_groups(&id);
+        match state.sessions.remove(&id) {
+            Some(session) => session,
+            None => return Ok(None),
+        }
+    };
+
+    let grace_period = Duration::from_millis(grace_period_ms);
+    // Terminating takes up to twice the grace period, don't hold the session meanwhile
+    let closer = lock_session(&session).closer();
+    let result = tokio::task::spawn_blocking(move || closer.terminate(grace_period))
+        .await
+        .map_err(|e| format!("Close failed: {}", e))?;
+    Ok(Some(result))
+}
+
+/// Get the recent output of a terminal session so a frontend can replay it on reattach
+#[tauri::command]
+async fn get_session_snapshot(
+    id: String,
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<ScrollbackSnapshot, String> {
+    let client = state.lock().unwrap().daemon_client(&id);
+    if let Some(client) = client {
+        return match client.request(DaemonRequest::Snapshot { id }).await? {
+            DaemonReply::Snapshot { snapshot } => Ok(snapshot),
+            reply => Err(format!("Unexpected reply from session daemon: {:?}", reply)),
+        };
+    }
+
+    let session = {
+        let state = state.lock().unwrap();
+        state
+            .sessions
+            .get(&id)
+            .ok_or_else(|| "Session not found".to_string())?
+            .clone()
+    };
+
+    let snapshot = lock_session(&session).snapshot();
+    Ok(snapshot)
+}
+
+/// Get the parsed screen of a terminal session: visible lines, cursor, alternate screen and modes
+/// Lines that scrolled off the primary screen are included when `include_scrollback` is set.
+#[tauri::command]
+async fn get_screen_snapshot(
+    id: String,
+    include_scrollback: Option<bool>,
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<ScreenSnapshot, String> {
+    let include_scrollback = include_scrollback.unwrap_or(false);
+
+    let client = state.lock().unwrap().daemon_client(&id);
+    if let Some(client) = client {
+        return match client.request(DaemonRequest::Screen { id, include_scrollback }).await? {
+            DaemonReply::Screen { screen } => Ok(screen),
+            reply => Err(format!("Unexpected reply from session daemon: {:?}", reply)),
+        };
+    }
+
+    let session = {
+        let state = state.lock().unwrap();
+        state
+            .sessions
+            .get(&id)
+            .ok_or_else(|| "Session not found".to_string())?
+            .clone()
+    };
+
+    let snapshot = lock_session(&session).screen_snapshot(include_scrollback);
+    Ok(snapshot)
+}
+
+/// Start recording a session to an asciicast v2 file
+/// Output, input sent through `write_to_session` and resizes are recorded until
+/// `stop_recording` is called or the session ends, which sends the recording's info
+/// as `terminal-recording-finished-{id}`.
+#[tauri::command]
+async fn start_recording(
+    id: String,
+    path: String,
     state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
 ) -> Result<(), String> {
-    let mut state = state.lock().unwrap();
-    state.sessions.remove(&id);
-    state.writers.remove(&id);
-    state.session_pids.remove(&id);
-    Ok(())
+    let client = state.lock().unwrap().daemon_client(&id);
+    if let Some(client) = client {
+        return client.request(DaemonRequest::StartRecording { id, path }).await.map(|_| ());
+    }
+
+    let session = {
+        let state = state.lock().unwrap();
+        state
+            .sessions
+            .get(&id)
+            .ok_or_else(|| "Session not found".to_string())?
+            .clone()
+    };
+
+    let session = lock_session(&session);
+    session
+        .start_recording(std::path::Path::new(&path))
+        .map_err(|e| format!("Failed to start recording: {}", e))
+}
+
+/// Stop recording a session, returns None if it wasn't being recorded
+#[tauri::command]
+async fn stop_recording(
+    id: String,
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<Option<RecordingInfo>, String> {
+    let client = state.lock().unwrap().daemon_client(&id);
+    if let Some(client) = client {
+        return match client.request(DaemonRequest::StopRecording { id }).await? {
+            DaemonReply::Recording { info } => Ok(info),
+            reply => Err(format!("Unexpected reply from session daemon: {:?}", reply)),
+        };
+    }
+
+    let session = {
+        let state = state.lock().unwrap();
+        state
+            .sessions
+            .get(&id)
+            .ok_or_else(|| "Session not found".to_string())?
+            .clone()
+    };
+
+    let session = lock_session(&session);
+    session
+        .stop_recording()
+        .map_err(|e| format!("Failed to stop recording: {}", e))
+}
+
+/// Write a session's output to `path` from now on, as plain text or HTML, rotating by size
+#[tauri::command]
+async fn start_session_log(
+    id: String,
+    path: String,
+    options: Option<LogOptions>,
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<(), String> {
+    let options = options.unwrap_or_default();
+    let client = state.lock().unwrap().daemon_client(&id);
+    if let Some(client) = client {
+        let request = DaemonRequest::StartLog { id, path, options };
+        return client.request(request).await.map(|_| ());
+    }
+
+    let session = {
+        let state = state.lock().unwrap();
+        state
+            .sessions
+            .get(&id)
+            .ok_or_else(|| "Session not found".to_string())?
+            .clone()
+    };
+
+    let session = lock_session(&session);
+    session
+        .start_log(std::path::Path::new(&path), options)
+        .map_err(|e| format!("Failed to start log: {}", e))
+}
+
+/// Stop logging a session's output
+#[tauri::command]
+async fn stop_session_log(
+    id: String,
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<(), String> {
+    let client = state.lock().unwrap().daemon_client(&id);
+    if let Some(client) = client {
+        return client.request(DaemonRequest::StopLog { id }).await.map(|_| ());
+    }
+
+    let session = {
+        let state = state.lock().unwrap();
+        state
+            .sessions
+            .get(&id)
+            .ok_or_else(|| "Session not found".to_string())?
+            .clone()
+    };
+
+    let session = lock_session(&session);
+    session
+        .stop_log()
+        .map(|_| ())
+        .map_err(|e| format!("Failed to stop log: {}", e))
 }
 
-/// Get process information for a terminal session
+/// Write a session's buffered history to `path` as plain text or HTML
+/// Returns the number of bytes written.
+#[tauri::command]
+async fn export_session_log(
+    id: String,
+    path: String,
+    format: Option<LogFormat>,
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<u64, String> {
+    let client = state.lock().unwrap().daemon_client(&id);
+    let snapshot = if let Some(client) = client {
+        match client.request(DaemonRequest::Snapshot { id }).await? {
+            DaemonReply::Snapshot { snapshot } => snapshot,
+            reply => return Err(format!("Unexpected reply from session daemon: {:?}", reply)),
+        }
+    } else {
+        let session = {
+            let state = state.lock().unwrap();
+            state
+                .sessions
+                .get(&id)
+                .ok_or_else(|| "Session not found".to_string())?
+                .clone()
+        };
+        let snapshot = lock_session(&session).snapshot();
+        snapshot
+    };
+
+    let log = tip_term::terminal::session_log::convert_log(&snapshot.data, format.unwrap_or_default());
+    std::fs::write(&path, &log).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    Ok(log.len() as u64)
+}
+
+/// Get the foreground job of a terminal session: name, cwd, PID, argv and whether it's the shell
 #[tauri::command]
 async fn get_session_info(
     id: String,
     state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
 ) -> Result<Option<ProcessInfo>, String> {
-    let child_pid = {
+    let client = state.lock().unwrap().daemon_client(&id);
+    if let Some(client) = client {
+        return match client.request(DaemonRequest::ProcessInfo { id }).await? {
+            DaemonReply::Process { info } => Ok(info),
+            reply => Err(format!("Unexpected reply from session daemon: {:?}", reply)),
+        };
+    }
+
+    let session = {
         let state = state.lock().unwrap();
-        *state
-            .session_pids
+        if state.playbacks.contains_key(&id) {
+            return Ok(None);
+        }
+        state
+            .sessions
             .get(&id)
             .ok_or_else(|| "Session not found".to_string())?
+            .clone()
     };
 
-    let info = tip_term::get_process_info_by_pid(child_pid);
+    let info = lock_session(&session).get_process_info();
     Ok(info)
 }
 
+/// Get the session's shell and its whole descendant tree, with command lines and resource usage
+#[tauri::command]
+async fn get_session_process_tree(
+    id: String,
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<Option<ProcessNode>, String> {
+    let client = state.lock().unwrap().daemon_client(&id);
+    if let Some(client) = client {
+        return match client.request(DaemonRequest::ProcessTree { id }).await? {
+            DaemonReply::ProcessTree { tree } => Ok(tree),
+            reply => Err(format!("Unexpected reply from session daemon: {:?}", reply)),
+        };
+    }
+
+    let session = {
+        let state = state.lock().unwrap();
+        if state.playbacks.contains_key(&id) {
+            return Ok(None);
+        }
+        state
+            .sessions
+            .get(&id)
+            .ok_or_else(|| "Session not found".to_string())?
+            .clone()
+    };
+
+    // A full process scan, keep it off the async runtime and out of the session lock
+    let processes = lock_session(&session).processes();
+    tokio::task::spawn_blocking(move || processes.tree())
+        .await
+        .map_err(|e| format!("Failed to read process tree: {}", e))
+}
+
+/// Send INT, TERM, KILL, STOP, CONT or QUIT to a session's foreground job
+/// With `pid`, the signal goes to that process instead, which must be in the session's tree.
+/// Returns the process group or PID that was signalled.
+#[tauri::command]
+async fn signal_session(
+    id: String,
+    signal: SessionSignal,
+    pid: Option<u32>,
+    state: tauri::State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<u32, String> {
+    let client = state.lock().unwrap().daemon_client(&id);
+    if let Some(client) = client {
+        return match client.request(DaemonRequest::Signal { id, signal, pid }).await? {
+            DaemonReply::Signalled { target } => Ok(target),
+            reply => Err(format!("Unexpected reply from session daemon: {:?}", reply)),
+        };
+    }
+
+    let session = {
+        let state = state.lock().unwrap();
+        if state.playbacks.contains_key(&id) {
+            return Err("Session is read-only".to_string());
+        }
+        state
+            .sessions
+            .get(&id)
+            .ok_or_else(|| "Session not found".to_string())?
+            .clone()
+    };
+
+    // Checking `pid` against the process tree scans every process
+    let processes = lock_session(&session).processes();
+    tokio::task::spawn_blocking(move || processes.signal(signal, pid))
+        .await
+        .map_err(|e| format!("Signal failed: {}", e))?
+        .map_err(|e| format!("Signal failed: {}", e))
+}
+
 /// Get shell history suggestions (zsh)
 #[tauri::command]
 async fn get_shell_history(
@@ -220,21 +924,87 @@ fn start_dragging(window: tauri::Window) -> Result<(), String> {
 }
 
 fn main() {
+    // The app binary doubles as the session daemon: `tip-term --session-daemon [socket]`
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some(daemon::DAEMON_FLAG) {
+        let socket_path = match args.get(2) {
+            Some(path) => Ok(std::path::PathBuf::from(path)),
+            None => daemon::default_socket_path(),
+        };
+        if let Err(e) = socket_path.and_then(|path| daemon::run(&path)) {
+            eprintln!("Session daemon failed: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_notification::init())
         .setup(|app| {
             app.manage(Arc::new(Mutex::new(TerminalState::new())));
             app.manage(Arc::new(Mutex::new(ConfigWatchState::new())));
+            app.manage(Arc::new(Mutex::new(FocusState::new())));
+            let output = Arc::new(OutputMultiplexer::default());
+            app.manage(output.clone());
+            tauri::async_runtime::spawn(output::run_output_frames(output, app.handle().clone()));
+            let restore_state = match persistence::sessions_dir(app.handle()) {
+                Ok(dir) => RestoreState::load(dir),
+                Err(e) => {
+                    eprintln!("Session restore disabled: {}", e);
+                    RestoreState::new()
+                }
+            };
+            app.manage(Arc::new(Mutex::new(restore_state)));
             Ok(())
         })
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::Focused(focused) = event {
+                let state = window.state::<Arc<Mutex<FocusState>>>();
+                state.lock().unwrap().window_focused = *focused;
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             create_session,
             write_to_session,
+            write_bytes_to_session,
+            set_session_input_encoding,
+            paste_to_session,
+            output::subscribe_output,
+            ack_session_output,
+            reset_session_output_window,
             resize_terminal,
             close_session,
             get_session_info,
+            get_session_process_tree,
+            signal_session,
+            metrics::get_session_metrics,
+            metrics::set_session_metrics_interval,
+            get_session_snapshot,
+            get_screen_snapshot,
+            set_session_title,
+            start_recording,
+            stop_recording,
+            playback::play_recording,
+            playback::start_playback,
+            start_session_log,
+            stop_session_log,
+            export_session_log,
+            persistence::get_restorable_sessions,
+            persistence::restore_session,
+            persistence::discard_restorable_sessions,
+            session_daemon::start_session_daemon,
+            session_daemon::list_daemon_sessions,
+            session_daemon::attach_session,
+            session_daemon::detach_session,
             config::start_terminal_config_watcher,
+            notifications::set_active_session,
+            groups::create_session_group,
+            groups::delete_session_group,
+            groups::add_to_session_group,
+            groups::remove_from_session_group,
+            groups::list_session_groups,
+            groups::broadcast_to_group,
             filesystem::read_directory,
             filesystem::read_file,
             filesystem::write_file,
@@ -261,6 +1031,13 @@ fn main() {
             start_dragging,
             get_shell_history,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                if let Err(e) = persistence::save_sessions(app) {
+                    eprintln!("Failed to save sessions: {}", e);
+                }
+            }
+        });
 }
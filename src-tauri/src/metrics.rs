@@ -0,0 +1,93 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tauri::{AppHandle, Emitter, Manager, State};
+use tip_term::terminal::daemon::{DaemonReply, DaemonRequest};
+use tip_term::{lock_session, SessionMetricsSnapshot};
+
+use crate::{SessionEvent, TerminalState};
+
+/// Shortest interval accepted for the `session-metrics` event
+const MIN_METRICS_INTERVAL_MS: u64 = 100;
+
+async fn session_metrics(
+    state: &Mutex<TerminalState>,
+    id: String,
+) -> Result<SessionMetricsSnapshot, String> {
+    let client = state.lock().unwrap().daemon_client(&id);
+    if let Some(client) = client {
+        return match client.request(DaemonRequest::Metrics { id }).await? {
+            DaemonReply::Metrics { metrics } => Ok(metrics),
+            reply => Err(format!("Unexpected reply from session daemon: {:?}", reply)),
+        };
+    }
+
+    let session = state
+        .lock()
+        .unwrap()
+        .sessions
+        .get(&id)
+        .cloned()
+        .ok_or_else(|| "Session not found".to_string())?;
+    let metrics = lock_session(&session).metrics();
+    Ok(metrics.snapshot())
+}
+
+/// Throughput and latency counters of a session since it started
+#[tauri::command]
+pub async fn get_session_metrics(
+    id: String,
+    state: State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<SessionMetricsSnapshot, String> {
+    session_metrics(&state, id).await
+}
+
+/// Emit `session-metrics` with the counters of every session each `interval_ms`,
+/// or stop when it is missing or 0
+#[tauri::command]
+pub async fn set_session_metrics_interval(
+    interval_ms: Option<u64>,
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<(), String> {
+    let mut state = state.lock().unwrap();
+    if let Some(task) = state.metrics_task.take() {
+        task.abort();
+    }
+    let interval_ms = match interval_ms {
+        Some(0) | None => return Ok(()),
+        Some(interval_ms) => interval_ms.max(MIN_METRICS_INTERVAL_MS),
+    };
+    let task = tokio::spawn(emit_metrics(Duration::from_millis(interval_ms), app));
+    state.metrics_task = Some(task.abort_handle());
+    Ok(())
+}
+
+async fn emit_metrics(interval: Duration, app: AppHandle) {
+    let mut ticks = tokio::time::interval(interval);
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticks.tick().await;
+        let state = app.state::<Arc<Mutex<TerminalState>>>();
+        let ids: Vec<String> = {
+            let state = state.lock().unwrap();
+            state
+                .sessions
+                .keys()
+                .chain(state.daemon_sessions.iter())
+                .cloned()
+                .collect()
+        };
+
+        let mut metrics = Vec::with_capacity(ids.len());
+        for id in &ids {
+            // Sessions can go away between listing and asking
+            if let Ok(event) = session_metrics(&state, id.clone()).await {
+                metrics.push(SessionEvent { session_id: id, event });
+            }
+        }
+        if let Err(e) = app.emit("session-metrics", metrics) {
+            eprintln!("Failed to emit session metrics: {}", e);
+        }
+    }
+}
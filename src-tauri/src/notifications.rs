@@ -0,0 +1,82 @@
+use std::sync::{Arc, Mutex};
+
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_notification::NotificationExt;
+use tip_term::terminal::shell_integration::CommandFinished;
+
+use crate::config::ConfigWatchState;
+
+/// Which session the user is looking at, used to skip notifications for it
+pub struct FocusState {
+    pub window_focused: bool,
+    pub active_session: Option<String>,
+}
+
+impl FocusState {
+    pub fn new() -> Self {
+        Self {
+            window_focused: true,
+            active_session: None,
+        }
+    }
+
+    fn is_focused(&self, session_id: &str) -> bool {
+        self.window_focused && self.active_session.as_deref() == Some(session_id)
+    }
+}
+
+/// Format a duration as e.g. "42s", "3m 05s" or "1h 02m"
+fn format_duration(duration_ms: u64) -> String {
+    let secs = duration_ms / 1000;
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+/// Notify about a finished command if it ran long enough and its session isn't in view
+pub fn notify_command_finished(session_id: &str, finished: &CommandFinished, app: &AppHandle) {
+    let settings = {
+        let state = app.state::<Arc<Mutex<ConfigWatchState>>>();
+        let state = state.lock().unwrap();
+        state.backend.command_notifications.clone()
+    };
+    if !settings.enabled || finished.duration_ms < settings.min_duration_ms {
+        return;
+    }
+
+    let focused = {
+        let state = app.state::<Arc<Mutex<FocusState>>>();
+        let state = state.lock().unwrap();
+        state.is_focused(session_id)
+    };
+    if focused {
+        return;
+    }
+
+    let command = finished.command.as_deref().unwrap_or("Command");
+    let duration = format_duration(finished.duration_ms);
+    let (title, body) = match finished.exit_code {
+        Some(0) => ("Command finished", format!("{} finished after {}", command, duration)),
+        Some(code) => (
+            "Command failed",
+            format!("{} exited with {} after {}", command, code, duration),
+        ),
+        None => ("Command finished", format!("{} ended after {}", command, duration)),
+    };
+
+    if let Err(e) = app.notification().builder().title(title).body(body).show() {
+        eprintln!("Failed to show notification: {}", e);
+    }
+}
+
+/// Record which session is shown, None when no terminal session is active
+#[tauri::command]
+pub async fn set_active_session(
+    id: Option<String>,
+    state: State<'_, Arc<Mutex<FocusState>>>,
+) -> Result<(), String> {
+    state.lock().unwrap().active_session = id;
+    Ok(())
+}
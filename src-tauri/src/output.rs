@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tauri::ipc::{Channel, InvokeResponseBody};
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+use crate::terminal::{FlowControl, PtyOutputReceiver, SessionMetrics};
+
+/// How often pending output of all sessions is flushed to the frontend, about once per frame
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Output sent in one frame across all sessions, split fairly between them
+const FRAME_MAX_BYTES: usize = 1024 * 1024;
+
+/// Output a session may have waiting for a frame before its output loop stops reading
+pub const QUEUE_MAX_BYTES: usize = 256 * 1024;
+
+/// Output of one session waiting for the next frame
+struct SessionQueue {
+    data: Mutex<Vec<u8>>,
+    /// Local sessions wait for acks here, daemon sessions are flow controlled by the daemon
+    flow: Option<Arc<FlowControl>>,
+    /// Counts what is emitted, daemon sessions count in the daemon
+    metrics: Option<Arc<SessionMetrics>>,
+    /// Bytes queued or taken into a frame that hasn't been sent yet
+    unsent: AtomicUsize,
+    /// Notified whenever a frame with data from the queue was sent
+    drained: Notify,
+}
+
+impl SessionQueue {
+    fn is_open(&self) -> bool {
+        self.flow.as_ref().is_none_or(|flow| flow.is_open())
+    }
+
+    fn is_ready(&self) -> bool {
+        self.is_open() && !self.data.lock().unwrap().is_empty()
+    }
+
+    /// Release bytes taken into a frame, they count against the window only if it went out
+    fn sent(&self, bytes: usize, delivered: bool) {
+        if delivered {
+            if let Some(flow) = &self.flow {
+                flow.sent(bytes as u64);
+            }
+            if let Some(metrics) = &self.metrics {
+                metrics.record_emit(bytes);
+            }
+        }
+        self.unsent.fetch_sub(bytes, Ordering::SeqCst);
+        self.drained.notify_waiters();
+    }
+}
+
+/// Output taken from the queues for one frame, with the queue each part came from
+type Frame = Vec<(Arc<SessionQueue>, (String, Vec<u8>))>;
+
+/// Collects the output of every session and flushes it as one message per frame
+/// Every frame, sessions with little output get all of it sent and the noisy ones
+/// split what is left of the frame, so a flooding pane can't starve the others.
+#[derive(Default)]
+pub struct OutputMultiplexer {
+    queues: Mutex<HashMap<String, Arc<SessionQueue>>>,
+    /// Registered by the frontend with `subscribe_output`
+    channel: Mutex<Option<Channel<InvokeResponseBody>>>,
+    /// Notified when a session queues output or acks reopen its window
+    pending: Arc<Notify>,
+}
+
+impl OutputMultiplexer {
+    /// Output of `session_id` waiting for a frame
+    pub fn queued_bytes(&self, session_id: &str) -> usize {
+        let queues = self.queues.lock().unwrap();
+        queues
+            .get(session_id)
+            .map_or(0, |queue| queue.data.lock().unwrap().len())
+    }
+
+    /// Deliver frames through `channel` from now on, replacing an earlier subscription
+    pub fn subscribe(&self, channel: Channel<InvokeResponseBody>) {
+        *self.channel.lock().unwrap() = Some(channel);
+    }
+
+    /// Some session has output queued and may send it
+    fn has_ready(&self) -> bool {
+        let queues = self.queues.lock().unwrap();
+        queues.values().any(|queue| queue.is_ready())
+    }
+
+    /// Take this frame's share of every session's queued output
+    fn take_frame(&self) -> Frame {
+        let mut ready: Vec<(String, Arc<SessionQueue>, usize)> = {
+            let queues = self.queues.lock().unwrap();
+            queues
+                .iter()
+                .filter(|(_, queue)| queue.is_open())
+                .map(|(id, queue)| (id.clone(), queue.clone(), queue.data.lock().unwrap().len()))
+                .filter(|(_, _, len)| *len > 0)
+                .collect()
+        };
+        // Smallest first, so what a quiet session doesn't need goes to the noisy ones
+        ready.sort_by_key(|(_, _, len)| *len);
+
+        let mut budget = FRAME_MAX_BYTES;
+        let mut frame = Vec::with_capacity(ready.len());
+        let count = ready.len();
+        for (index, (id, queue, len)) in ready.into_iter().enumerate() {
+            let share = (budget / (count - index)).min(len);
+            let data: Vec<u8> = queue.data.lock().unwrap().drain(..share).collect();
+            budget -= data.len();
+            frame.push((queue, (id, data)));
+        }
+        frame
+    }
+
+    /// Queue a chunk of a session's output, waiting while its queue is full
+    async fn push(&self, queue: &SessionQueue, chunk: &[u8]) {
+        loop {
+            let drained = queue.drained.notified();
+            {
+                let mut data = queue.data.lock().unwrap();
+                if data.len() < QUEUE_MAX_BYTES {
+                    data.extend_from_slice(chunk);
+                    queue.unsent.fetch_add(chunk.len(), Ordering::SeqCst);
+                    break;
+                }
+            }
+            drained.await;
+        }
+        self.pending.notify_one();
+    }
+
+    /// Send a frame through the subscribed channel, or as `terminal-output-{id}` events without one
+    fn send<R: Runtime>(&self, app: &AppHandle<R>, frame: &[(String, Vec<u8>)]) -> tauri::Result<()> {
+        let channel = self.channel.lock().unwrap().clone();
+        match channel {
+            Some(channel) => channel.send(InvokeResponseBody::Raw(encode_frame(frame))),
+            None => {
+                for (id, data) in frame {
+                    app.emit(&format!("terminal-output-{}", id), data)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Frame layout: for each session, the id length as one byte, the id, the data length as a
+/// little-endian u32 and the data
+fn encode_frame(frame: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let size = frame.iter().map(|(id, data)| 5 + id.len() + data.len()).sum();
+    let mut encoded = Vec::with_capacity(size);
+    for (id, data) in frame {
+        encoded.push(id.len() as u8);
+        encoded.extend_from_slice(id.as_bytes());
+        encoded.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        encoded.extend_from_slice(data);
+    }
+    encoded
+}
+
+/// Flush queued output once per frame for as long as the app runs
+/// The first output after an idle period is flushed right away, so echo isn't delayed.
+/// While every session with output is waiting for acks, the loop sleeps until one is acked.
+pub async fn run_output_frames<R: Runtime>(mux: Arc<OutputMultiplexer>, app: AppHandle<R>) {
+    let mut last_flush = Instant::now() - FRAME_INTERVAL;
+    loop {
+        let pending = mux.pending.notified();
+        if !mux.has_ready() {
+            pending.await;
+        }
+        tokio::time::sleep_until(last_flush + FRAME_INTERVAL).await;
+        last_flush = Instant::now();
+
+        // Empty after a stale wakeup, e.g. for output an earlier frame already took
+        let frame = mux.take_frame();
+        if frame.is_empty() {
+            continue;
+        }
+        let (queues, output): (Vec<_>, Vec<_>) = frame.into_iter().unzip();
+        let result = mux.send(&app, &output);
+        if let Err(e) = &result {
+            eprintln!("Failed to emit terminal output: {}", e);
+        }
+        // Only now is the output on its way, a waiting `forward_output` may report the exit.
+        // Output that failed to go out is dropped and never acked, so it isn't counted as sent.
+        for (queue, (_, data)) in queues.iter().zip(&output) {
+            queue.sent(data.len(), result.is_ok());
+        }
+    }
+}
+
+/// Feed a session's output into the multiplexer until its channel closes
+/// Returns once everything it read has been sent. With `flow`, the session gets no frame
+/// share while the frontend is behind on acks, its queue fills up and the reader thread
+/// blocks, so the PTY pushes back on the program. Once the PTY is closed, what is left goes
+/// out regardless of acks, so the exit is reported even if the frontend stopped acking.
+pub async fn forward_output<R: Runtime>(
+    session_id: &str,
+    output_rx: &mut PtyOutputReceiver,
+    flow: Option<Arc<FlowControl>>,
+    metrics: Option<Arc<SessionMetrics>>,
+    app: &AppHandle<R>,
+) {
+    let mux = app.state::<Arc<OutputMultiplexer>>().inner().clone();
+    let queue = Arc::new(SessionQueue {
+        data: Mutex::new(Vec::new()),
+        flow,
+        metrics,
+        unsent: AtomicUsize::new(0),
+        drained: Notify::new(),
+    });
+    if let Some(flow) = &queue.flow {
+        flow.watch(mux.pending.clone());
+    }
+    mux.queues
+        .lock()
+        .unwrap()
+        .insert(session_id.to_string(), queue.clone());
+
+    while let Some(chunk) = output_rx.recv().await {
+        mux.push(&queue, &chunk).await;
+    }
+
+    // Let the last frames go out before the caller reports the exit
+    if let Some(flow) = &queue.flow {
+        flow.disable();
+    }
+    loop {
+        let drained = queue.drained.notified();
+        if queue.unsent.load(Ordering::SeqCst) == 0 {
+            break;
+        }
+        drained.await;
+    }
+    let mut queues = mux.queues.lock().unwrap();
+    if queues.get(session_id).is_some_and(|current| Arc::ptr_eq(current, &queue)) {
+        queues.remove(session_id);
+    }
+}
+
+/// Send output that doesn't come from a PTY, e.g. a playback, right away as its own frame
+pub fn send_output<R: Runtime>(app: &AppHandle<R>, session_id: &str, data: Vec<u8>) -> tauri::Result<()> {
+    let mux = app.state::<Arc<OutputMultiplexer>>();
+    mux.send(app, &[(session_id.to_string(), data)])
+}
+
+/// Deliver all sessions' output as binary frames through `channel` instead of
+/// `terminal-output-{id}` events. A later subscription replaces the earlier one,
+/// e.g. after the webview reloaded.
+#[tauri::command]
+pub async fn subscribe_output(
+    channel: Channel<InvokeResponseBody>,
+    mux: State<'_, Arc<OutputMultiplexer>>,
+) -> Result<(), String> {
+    mux.subscribe(channel);
+    Ok(())
+}
@@ -0,0 +1,298 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+use tip_term::{lock_session, SessionKind, SessionSpec};
+
+use crate::TerminalState;
+
+const MANIFEST_VERSION: u32 = 1;
+const MANIFEST_FILE: &str = "manifest.json";
+const SCROLLBACK_EXTENSION: &str = "scrollback";
+
+/// Bytes of scrollback saved per session
+const PERSISTED_SCROLLBACK_BYTES: usize = 256 * 1024;
+
+/// Written after the restored history so the new prompt starts on a clean line of the
+/// primary screen, with default attributes, a visible cursor, no scroll region and none of
+/// the mouse or bracketed paste modes a program (vim, less) may have left on
+const RESTORE_SEPARATOR: &[u8] = b"\x1b[?1049l\x1b[?1000l\x1b[?1002l\x1b[?1003l\x1b[?1006l\x1b[?2004l\x1b[r\
+\x1b[0m\x1b[?25h\r\n\x1b[2m--- Session restored ---\x1b[0m\r\n";
+
+/// Sessions that were open when the app last quit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionManifest {
+    pub version: u32,
+    /// Unix timestamp (seconds) of when the manifest was written
+    pub saved_at: u64,
+    pub sessions: Vec<PersistedSession>,
+}
+
+/// A single saved session, its scrollback lives next to the manifest in `<id>.scrollback`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PersistedSession {
+    pub id: String,
+    pub shell: String,
+    /// Missing in manifests written before command sessions existed
+    #[serde(default)]
+    pub kind: SessionKind,
+    /// Arguments of the program, the whole command line after it for command sessions
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub cwd: Option<String>,
+    pub title: Option<String>,
+}
+
+/// New session created from a saved one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoredSession {
+    pub id: String,
+    /// Saved output to write into the terminal before the new shell's output
+    pub history: Vec<u8>,
+}
+
+/// Manifest loaded at startup, waiting for the frontend to restore or discard it
+pub struct RestoreState {
+    pub dir: Option<PathBuf>,
+    pub manifest: Option<SessionManifest>,
+}
+
+impl RestoreState {
+    pub fn new() -> Self {
+        Self {
+            dir: None,
+            manifest: None,
+        }
+    }
+
+    /// Load the manifest saved by the previous run, if any
+    pub fn load(dir: PathBuf) -> Self {
+        let manifest = match read_manifest(&dir) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                eprintln!("Failed to load session manifest: {}", e);
+                None
+            }
+        };
+
+        Self {
+            dir: Some(dir),
+            manifest,
+        }
+    }
+}
+
+/// Directory that holds the manifest and scrollback files
+pub fn sessions_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join("sessions"))
+        .map_err(|e| format!("Failed to resolve app data dir: {}", e))
+}
+
+fn read_manifest(dir: &Path) -> Result<Option<SessionManifest>, String> {
+    let path = dir.join(MANIFEST_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let manifest: SessionManifest = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+
+    if manifest.version != MANIFEST_VERSION || manifest.sessions.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(manifest))
+}
+
+fn scrollback_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("{}.{}", id, SCROLLBACK_EXTENSION))
+}
+
+/// Remove the manifest and every scrollback file except those of `keep`
+fn clear_dir(dir: &Path, keep: &[PersistedSession]) -> Result<(), String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(()),
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_session_file = path.file_name().is_some_and(|name| name == MANIFEST_FILE)
+            || path.extension().is_some_and(|ext| ext == SCROLLBACK_EXTENSION);
+        let is_kept = keep.iter().any(|saved| path == scrollback_path(dir, &saved.id));
+        if is_session_file && !is_kept {
+            fs::remove_file(&path)
+                .map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Save every open session so it can be offered for restore on the next launch
+/// Called when the app exits. Saved sessions of the previous run that were neither
+/// restored nor discarded are kept, so they are offered again.
+pub fn save_sessions(app: &AppHandle) -> Result<(), String> {
+    let dir = sessions_dir(app)?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let pending: Vec<PersistedSession> = {
+        let restore_state = app.state::<Arc<Mutex<RestoreState>>>();
+        let restore_state = restore_state.lock().unwrap();
+        restore_state
+            .manifest
+            .as_ref()
+            .map(|manifest| manifest.sessions.clone())
+            .unwrap_or_default()
+    };
+    clear_dir(&dir, &pending)?;
+
+    let sessions: Vec<_> = {
+        let state = app.state::<Arc<Mutex<TerminalState>>>();
+        let state = state.lock().unwrap();
+        state
+            .sessions
+            .iter()
+            .map(|(id, session)| (id.clone(), session.clone()))
+            .collect()
+    };
+
+    let mut persisted = pending;
+    persisted.reserve(sessions.len());
+    for (id, session) in sessions {
+        let (shell, kind, args, title, child_pid, snapshot) = {
+            let session = lock_session(&session);
+            (
+                session.shell().to_string(),
+                session.kind(),
+                session.args().to_vec(),
+                session.title().map(|t| t.to_string()),
+                session.child_pid(),
+                session.snapshot_tail(PERSISTED_SCROLLBACK_BYTES),
+            )
+        };
+
+        if !snapshot.data.is_empty() {
+            let path = scrollback_path(&dir, &id);
+            fs::write(&path, &snapshot.data)
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        }
+
+        let cwd = tip_term::get_process_info_by_pid(child_pid).map(|info| info.cwd);
+        persisted.push(PersistedSession {
+            id,
+            shell,
+            kind,
+            args,
+            cwd,
+            title,
+        });
+    }
+
+    if persisted.is_empty() {
+        return Ok(());
+    }
+
+    let manifest = SessionManifest {
+        version: MANIFEST_VERSION,
+        saved_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        sessions: persisted,
+    };
+    let content = serde_json::to_string_pretty(&manifest)
+        .map_err(|e| format!("Failed to serialize session manifest: {}", e))?;
+
+    // Write to a temp file first so a crash mid-write can't leave a truncated manifest
+    let path = dir.join(MANIFEST_FILE);
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, content)
+        .map_err(|e| format!("Failed to write {}: {}", tmp_path.display(), e))?;
+    fs::rename(&tmp_path, &path)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+    Ok(())
+}
+
+/// List the sessions saved by the previous run
+#[tauri::command]
+pub async fn get_restorable_sessions(
+    state: State<'_, Arc<Mutex<RestoreState>>>,
+) -> Result<Option<SessionManifest>, String> {
+    let state = state.lock().unwrap();
+    Ok(state.manifest.clone())
+}
+
+/// Recreate a saved session in its last directory
+/// The saved scrollback is returned as `history` and also seeded into the new session's
+/// scrollback, so it shows up above the new prompt and survives the next save.
+#[tauri::command]
+pub async fn restore_session(
+    id: String,
+    spec: Option<SessionSpec>,
+    app: AppHandle,
+    restore_state: State<'_, Arc<Mutex<RestoreState>>>,
+    terminal_state: State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<RestoredSession, String> {
+    let (saved, dir) = {
+        let restore_state = restore_state.lock().unwrap();
+        let saved = restore_state
+            .manifest
+            .as_ref()
+            .ok_or_else(|| "No saved sessions".to_string())?
+            .sessions
+            .iter()
+            .find(|s| s.id == id)
+            .cloned()
+            .ok_or_else(|| "Saved session not found".to_string())?;
+        (saved, restore_state.dir.clone())
+    };
+
+    let mut history = dir
+        .map(|dir| scrollback_path(&dir, &saved.id))
+        .and_then(|path| fs::read(path).ok())
+        .unwrap_or_default();
+    if !history.is_empty() {
+        history.extend_from_slice(RESTORE_SEPARATOR);
+    }
+
+    let mut spec = spec.unwrap_or_default();
+    spec.kind = saved.kind;
+    spec.args = saved.args;
+    if spec.cwd.is_none() {
+        spec.cwd = saved.cwd.clone();
+    }
+    spec.triggers = crate::triggers::configured_triggers(&app);
+
+    let new_id = crate::spawn_session(saved.shell, spec, &history, saved.title, &app, &terminal_state)?;
+
+    // Kept until now so a session that failed to spawn can be restored or discarded later
+    if let Some(manifest) = restore_state.lock().unwrap().manifest.as_mut() {
+        manifest.sessions.retain(|s| s.id != id);
+    }
+    Ok(RestoredSession {
+        id: new_id,
+        history,
+    })
+}
+
+/// Forget the sessions saved by the previous run
+#[tauri::command]
+pub async fn discard_restorable_sessions(
+    state: State<'_, Arc<Mutex<RestoreState>>>,
+) -> Result<(), String> {
+    let mut state = state.lock().unwrap();
+    state.manifest = None;
+    if let Some(dir) = &state.dir {
+        clear_dir(dir, &[])?;
+    }
+    Ok(())
+}
@@ -0,0 +1,171 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tip_term::terminal::recording::{read_cast, CastEvent};
+use tip_term::SessionExitStatus;
+use tokio::sync::oneshot;
+use tokio::task::AbortHandle;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::TerminalState;
+
+/// Longest playback at the requested speed, longer ones are rejected
+const MAX_PLAYBACK_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Playback task of a read-only session, kept in `TerminalState::playbacks`
+pub struct Playback {
+    task: AbortHandle,
+    /// Taken by `start_playback`, the task waits for it before sending any output
+    start: Option<oneshot::Sender<()>>,
+}
+
+impl Playback {
+    pub fn abort(&self) {
+        self.task.abort();
+    }
+}
+
+/// Read-only session created by `play_recording`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaybackInfo {
+    pub id: String,
+    pub cols: u16,
+    pub rows: u16,
+    /// Playback length at the requested speed
+    pub duration_ms: u64,
+}
+
+/// Terminal size change during playback, sent as `terminal-playback-resize-{id}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaybackResize {
+    pub cols: u16,
+    pub rows: u16,
+}
+
+/// Event times with pauses longer than `idle_limit` shortened to it
+fn playback_times(events: &[CastEvent], idle_limit: Option<f64>) -> Vec<f64> {
+    let mut times = Vec::with_capacity(events.len());
+    let (mut previous, mut adjusted) = (0.0, 0.0);
+    for event in events {
+        let gap = (event.time() - previous).max(0.0);
+        adjusted += idle_limit.map_or(gap, |limit| gap.min(limit));
+        previous = event.time();
+        times.push(adjusted);
+    }
+    times
+}
+
+async fn run_playback(
+    id: String,
+    events: Vec<CastEvent>,
+    times: Vec<f64>,
+    speed: f64,
+    start: oneshot::Receiver<()>,
+    app: AppHandle,
+) {
+    let resize_event = format!("terminal-playback-resize-{}", id);
+
+    // Dropped with the playback if it is closed before it started
+    if start.await.is_err() {
+        return;
+    }
+    let start = Instant::now();
+    for (event, time) in events.iter().zip(times) {
+        tokio::time::sleep_until(start + Duration::from_secs_f64(time / speed)).await;
+
+        let result = match event.code() {
+            "o" => tip_term::output::send_output(&app, &id, event.data().as_bytes().to_vec()),
+            "r" => match event.resize() {
+                Some((cols, rows)) => app.emit(&resize_event, PlaybackResize { cols, rows }),
+                None => Ok(()),
+            },
+            // Input is already echoed in the output
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            eprintln!("Failed to emit playback event: {}", e);
+            break;
+        }
+    }
+
+    {
+        let state = app.state::<Arc<Mutex<TerminalState>>>();
+        state.lock().unwrap().playbacks.remove(&id);
+    }
+
+    let status = SessionExitStatus {
+        code: Some(0),
+        signal: None,
+        success: true,
+    };
+    if let Err(e) = app.emit(&format!("terminal-exit-{}", id), status) {
+        eprintln!("Failed to emit terminal exit: {}", e);
+    }
+}
+
+/// Play an asciicast v2 recording into a new read-only session
+/// `speed` scales playback, 2.0 plays twice as fast. Output arrives like a live session's once
+/// the frontend calls `start_playback`, and the session exits when the recording ends or is
+/// closed with `close_session`.
+#[tauri::command]
+pub async fn play_recording(
+    path: String,
+    speed: Option<f64>,
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<PlaybackInfo, String> {
+    let speed = speed.unwrap_or(1.0);
+    if !speed.is_finite() || speed <= 0.0 {
+        return Err(format!("Invalid playback speed: {}", speed));
+    }
+
+    let (header, events) = read_cast(Path::new(&path))?;
+    let times = playback_times(&events, header.idle_time_limit);
+    // Times only grow, so every event's offset in `run_playback` fits if the last one does
+    let duration = Duration::try_from_secs_f64(times.last().copied().unwrap_or(0.0) / speed)
+        .ok()
+        .filter(|duration| *duration <= MAX_PLAYBACK_DURATION)
+        .ok_or_else(|| format!("Recording is too long to play at speed {}", speed))?;
+
+    let id = Uuid::new_v4().to_string();
+    let (start_tx, start_rx) = oneshot::channel();
+    let task = tokio::spawn(run_playback(id.clone(), events, times, speed, start_rx, app));
+    let playback = Playback {
+        task: task.abort_handle(),
+        start: Some(start_tx),
+    };
+    state.lock().unwrap().playbacks.insert(id.clone(), playback);
+
+    Ok(PlaybackInfo {
+        id,
+        cols: header.width,
+        rows: header.height,
+        duration_ms: duration.as_millis() as u64,
+    })
+}
+
+/// Start a playback created by `play_recording`
+/// Called by the frontend once it listens for the session's output, so none of it is missed.
+#[tauri::command]
+pub async fn start_playback(
+    id: String,
+    state: State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<(), String> {
+    let start = state
+        .lock()
+        .unwrap()
+        .playbacks
+        .get_mut(&id)
+        .ok_or_else(|| "Playback not found".to_string())?
+        .start
+        .take()
+        .ok_or_else(|| "Playback already started".to_string())?;
+    // The task only goes away with the playback, which was just found
+    let _ = start.send(());
+    Ok(())
+}
@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tauri::{AppHandle, Emitter, Manager, State};
+use tip_term::terminal::daemon::{
+    default_socket_path, DaemonClient, DaemonEvent, DaemonReply, DaemonRequest, DaemonSessionInfo,
+};
+use tip_term::{ScrollbackSnapshot, SessionExitStatus, SessionSpec};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::TerminalState;
+
+/// Output chunks buffered per session between a route and the batcher
+const OUTPUT_ROUTE_CAPACITY: usize = 64;
+
+/// Output of one daemon session on its way to the output multiplexer
+/// The route is unbounded so a session whose output can't go out doesn't hold up the events
+/// of the others, the daemon's flow control window bounds what can pile up in it.
+struct OutputRoute {
+    output: mpsc::UnboundedSender<Vec<u8>>,
+    batcher: JoinHandle<()>,
+}
+
+impl OutputRoute {
+    fn new(id: String, app: AppHandle) -> Self {
+        let (output, mut route_rx) = mpsc::unbounded_channel::<Vec<u8>>();
+        let batcher = tokio::spawn(async move {
+            let (batch_tx, mut batch_rx) = mpsc::channel(OUTPUT_ROUTE_CAPACITY);
+            let relay = async move {
+                while let Some(data) = route_rx.recv().await {
+                    if batch_tx.send(data).await.is_err() {
+                        break;
+                    }
+                }
+            };
+            let forward = tip_term::output::forward_output(&id, &mut batch_rx, None, None, &app);
+            tokio::join!(relay, forward);
+        });
+        Self { output, batcher }
+    }
+}
+
+fn unexpected_reply(reply: DaemonReply) -> String {
+    format!("Unexpected reply from session daemon: {:?}", reply)
+}
+
+/// Create a session inside the daemon and track it as a proxied session
+pub async fn create_remote_session(
+    client: &DaemonClient,
+    shell: String,
+    spec: SessionSpec,
+    state: &Mutex<TerminalState>,
+) -> Result<String, String> {
+    match client.request(DaemonRequest::Create { shell, spec }).await? {
+        DaemonReply::Created { session } => {
+            let mut state = state.lock().unwrap();
+            state.daemon_sessions.insert(session.id.clone());
+            state.session_pids.insert(session.id.clone(), session.pid);
+            Ok(session.id)
+        }
+        reply => Err(unexpected_reply(reply)),
+    }
+}
+
+/// Forward daemon events to the frontend as the usual terminal events
+/// Output of each session goes through the output multiplexer like local sessions, flow control
+/// happens in the daemon so a session that is behind on acks doesn't hold up the others.
+async fn pump_events(mut events: mpsc::UnboundedReceiver<DaemonEvent>, app: AppHandle) {
+    let mut routes: HashMap<String, OutputRoute> = HashMap::new();
+
+    while let Some(event) = events.recv().await {
+        match event {
+            DaemonEvent::Output { id, data } => {
+                let route = routes
+                    .entry(id.clone())
+                    .or_insert_with(|| OutputRoute::new(id, app.clone()));
+                let _ = route.output.send(data);
+            }
+            DaemonEvent::Exit { id, status } => {
+                let route = routes.remove(&id);
+                tokio::spawn(report_exit(id, status, route, app.clone()));
+            }
+            // Dropping the route lets its batcher finish once the output sent so far is out
+            DaemonEvent::Detached { id } => drop(routes.remove(&id)),
+            DaemonEvent::RecordingFinished { id, info } => {
+                crate::emit_recording_finished(&id, info, &app)
+            }
+            DaemonEvent::Shell { id, event } => crate::emit_shell_event(&id, event, &app),
+            DaemonEvent::Process { id, info } => crate::emit_process_changed(&id, info, &app),
+            DaemonEvent::Trigger { id, event } => {
+                crate::triggers::handle_trigger_event(&id, event, &app)
+            }
+        }
+    }
+
+    // The daemon went away, its sessions can no longer be reached
+    let lost: Vec<String> = {
+        let state = app.state::<Arc<Mutex<TerminalState>>>();
+        let mut state = state.lock().unwrap();
+        state.daemon = None;
+        let lost: Vec<String> = state.daemon_sessions.drain().collect();
+        for id in &lost {
+            state.session_pids.remove(id);
+        }
+        lost
+    };
+    if let Err(e) = app.emit("session-daemon-disconnected", lost) {
+        eprintln!("Failed to emit daemon disconnect: {}", e);
+    }
+}
+
+/// Report a daemon session's exit once its last output went out
+async fn report_exit(
+    id: String,
+    status: SessionExitStatus,
+    route: Option<OutputRoute>,
+    app: AppHandle,
+) {
+    if let Some(OutputRoute { output, batcher }) = route {
+        drop(output);
+        let _ = batcher.await;
+    }
+
+    {
+        let state = app.state::<Arc<Mutex<TerminalState>>>();
+        let mut state = state.lock().unwrap();
+        state.daemon_sessions.remove(&id);
+        state.session_pids.remove(&id);
+        state.leave_groups(&id);
+    }
+
+    if let Err(e) = app.emit(&format!("terminal-exit-{}", id), status) {
+        eprintln!("Failed to emit terminal exit: {}", e);
+    }
+}
+
+/// Connect to the session daemon, starting it if needed
+/// Sessions created afterwards live in the daemon and survive an app restart.
+#[tauri::command]
+pub async fn start_session_daemon(
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<(), String> {
+    if state.lock().unwrap().daemon.is_some() {
+        return Ok(());
+    }
+
+    let (events_tx, events_rx) = mpsc::unbounded_channel();
+    let socket_path = default_socket_path()
+        .map_err(|e| format!("Failed to prepare the session daemon directory: {}", e))?;
+    let client = DaemonClient::connect_or_spawn(&socket_path, events_tx)
+        .await
+        .map_err(|e| format!("Failed to connect to session daemon: {}", e))?;
+
+    state.lock().unwrap().daemon = Some(Arc::new(client));
+    tokio::spawn(pump_events(events_rx, app));
+    Ok(())
+}
+
+/// List every session owned by the daemon, attached or not
+#[tauri::command]
+pub async fn list_daemon_sessions(
+    state: State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<Vec<DaemonSessionInfo>, String> {
+    let client = state
+        .lock()
+        .unwrap()
+        .daemon
+        .clone()
+        .ok_or_else(|| "Session daemon not connected".to_string())?;
+
+    match client.request(DaemonRequest::List).await? {
+        DaemonReply::Sessions { sessions } => Ok(sessions),
+        reply => Err(unexpected_reply(reply)),
+    }
+}
+
+/// Reattach to a daemon session, e.g. after the app restarted
+/// Returns the session's scrollback, live output follows as `terminal-output-{id}` events.
+#[tauri::command]
+pub async fn attach_session(
+    id: String,
+    state: State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<ScrollbackSnapshot, String> {
+    let client = state
+        .lock()
+        .unwrap()
+        .daemon
+        .clone()
+        .ok_or_else(|| "Session daemon not connected".to_string())?;
+
+    let snapshot = match client.request(DaemonRequest::Attach { id: id.clone() }).await? {
+        DaemonReply::Snapshot { snapshot } => snapshot,
+        reply => return Err(unexpected_reply(reply)),
+    };
+    let pid = match client.request(DaemonRequest::List).await? {
+        DaemonReply::Sessions { sessions } => sessions.into_iter().find(|s| s.id == id).map(|s| s.pid),
+        reply => return Err(unexpected_reply(reply)),
+    };
+
+    let mut state = state.lock().unwrap();
+    state.daemon_sessions.insert(id.clone());
+    if let Some(pid) = pid {
+        state.session_pids.insert(id, pid);
+    }
+    Ok(snapshot)
+}
+
+/// Stop showing a daemon session in this app, the session keeps running
+#[tauri::command]
+pub async fn detach_session(
+    id: String,
+    state: State<'_, Arc<Mutex<TerminalState>>>,
+) -> Result<(), String> {
+    let client = state
+        .lock()
+        .unwrap()
+        .daemon_client(&id)
+        .ok_or_else(|| "Session not found".to_string())?;
+
+    client.request(DaemonRequest::Detach { id: id.clone() }).await?;
+
+    let mut state = state.lock().unwrap();
+    state.daemon_sessions.remove(&id);
+    state.session_pids.remove(&id);
+    state.leave_groups(&id);
+    Ok(())
+}
@@ -0,0 +1,949 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+
+use super::flow_control::FlowControl;
+use super::metrics::SessionMetricsSnapshot;
+use super::paste::{self, PasteResult};
+use super::screen::ScreenSnapshot;
+use super::recording::RecordingInfo;
+use super::scrollback::ScrollbackSnapshot;
+use super::session_log::LogOptions;
+use super::shell_integration::ShellEvent;
+use super::spec::SessionSpec;
+use super::triggers::{TriggerConfig, TriggerEvent};
+use super::vte_parser::{
+    lock_session, watch_foreground_process, ProcessInfo, ProcessNode, PtyOutputReceiver,
+    SessionCloseResult, SessionExitStatus, SessionProcesses, SessionSignal, TerminalSession,
+};
+use super::writer::PtyWriter;
+
+/// Command line flag that makes the app binary run as the session daemon
+pub const DAEMON_FLAG: &str = "--session-daemon";
+
+const SOCKET_FILE: &str = "sessiond.sock";
+
+/// How often the daemon checks whether it has become idle and can exit
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long a client waits for a freshly spawned daemon to accept connections
+const SPAWN_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const SPAWN_CONNECT_RETRY: Duration = Duration::from_millis(50);
+
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Request sent by a client, one JSON line per request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum DaemonRequest {
+    /// Spawn a session, the requesting client is attached to it
+    Create { shell: String, spec: SessionSpec },
+    /// Raw bytes, written to the PTY unchanged
+    Write { id: String, data: Vec<u8> },
+    /// Text, encoded in the session's input encoding before it is written
+    WriteText { id: String, text: String },
+    SetInputEncoding { id: String, encoding: String },
+    /// Paste text, see `paste::paste`
+    Paste { id: String, text: String, confirmed: bool },
+    SetTriggers { id: String, triggers: Vec<TriggerConfig> },
+    /// The client rendered `bytes` of the session's output, see `FlowControl`
+    AckOutput { id: String, bytes: u64 },
+    /// Forget unacked output, e.g. after the client's frontend reloaded
+    ResetOutputWindow { id: String },
+    Resize { id: String, cols: u16, rows: u16 },
+    Close { id: String, grace_period_ms: u64 },
+    List,
+    /// Start streaming a session's output, replies with its scrollback
+    Attach { id: String },
+    /// Stop streaming a session's output, the session keeps running
+    Detach { id: String },
+    Snapshot { id: String },
+    Screen { id: String, include_scrollback: bool },
+    /// Foreground job of the session
+    ProcessInfo { id: String },
+    /// The session's shell and all its descendants
+    ProcessTree { id: String },
+    /// Signal the foreground job, or `pid` if it belongs to the session
+    Signal { id: String, signal: SessionSignal, pid: Option<u32> },
+    /// Throughput and latency counters of the session
+    Metrics { id: String },
+    /// Record the session to an asciicast file, the path is on the daemon's machine
+    StartRecording { id: String, path: String },
+    StopRecording { id: String },
+    /// Log the session's output to a file, the path is on the daemon's machine
+    StartLog { id: String, path: String, options: LogOptions },
+    StopLog { id: String },
+}
+
+/// Successful reply to a `DaemonRequest`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum DaemonReply {
+    Created { session: DaemonSessionInfo },
+    Sessions { sessions: Vec<DaemonSessionInfo> },
+    Snapshot { snapshot: ScrollbackSnapshot },
+    Screen { screen: ScreenSnapshot },
+    Recording { info: Option<RecordingInfo> },
+    Pasted { result: PasteResult },
+    Process { info: Option<ProcessInfo> },
+    ProcessTree { tree: Option<ProcessNode> },
+    Signalled { target: u32 },
+    Metrics { metrics: SessionMetricsSnapshot },
+    Closed { result: SessionCloseResult },
+    Done,
+}
+
+/// Output and lifecycle events pushed to attached clients
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum DaemonEvent {
+    Output {
+        id: String,
+        #[serde(with = "base64_bytes")]
+        data: Vec<u8>,
+    },
+    Exit { id: String, status: SessionExitStatus },
+    /// The session ended while being recorded, sent before `Exit`
+    RecordingFinished { id: String, info: RecordingInfo },
+    /// The connection detached from the session, no output for it follows
+    Detached { id: String },
+    Shell { id: String, event: ShellEvent },
+    Trigger { id: String, event: TriggerEvent },
+    /// The session's foreground process or its cwd changed
+    Process { id: String, info: ProcessInfo },
+}
+
+/// Output as a base64 string, a JSON array takes up to four bytes per byte and is slow to parse
+mod base64_bytes {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Session owned by the daemon, as reported by `list_daemon_sessions`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DaemonSessionInfo {
+    pub id: String,
+    pub shell: String,
+    pub pid: u32,
+    /// True if at least one client is streaming the session's output
+    pub attached: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RequestFrame {
+    req_id: u64,
+    request: DaemonRequest,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum ServerFrame {
+    Reply {
+        req_id: u64,
+        result: Result<DaemonReply, String>,
+    },
+    Event {
+        event: DaemonEvent,
+    },
+}
+
+/// Default socket location, private to the current user
+pub fn default_socket_path() -> std::io::Result<PathBuf> {
+    Ok(super::runtime_dir()?.join(SOCKET_FILE))
+}
+
+#[cfg(not(unix))]
+fn unsupported() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "The session daemon requires Unix domain sockets",
+    )
+}
+
+/// Connection attached to a session's output
+struct Subscriber {
+    frames: mpsc::UnboundedSender<ServerFrame>,
+    /// Output up to this byte offset was already delivered through the attach snapshot
+    start_offset: u64,
+}
+
+struct DaemonSession {
+    session: Arc<Mutex<TerminalSession>>,
+    writer: PtyWriter,
+    shell: String,
+    pid: u32,
+    subscribers: HashMap<u64, Subscriber>,
+    /// The session's output flow, shared by all subscribers
+    flow: Arc<FlowControl>,
+}
+
+impl DaemonSession {
+    fn info(&self, id: &str) -> DaemonSessionInfo {
+        DaemonSessionInfo {
+            id: id.to_string(),
+            shell: self.shell.clone(),
+            pid: self.pid,
+            attached: !self.subscribers.is_empty(),
+        }
+    }
+
+    fn unsubscribe(&mut self, connection_id: u64) {
+        if self.subscribers.remove(&connection_id).is_some() && self.subscribers.is_empty() {
+            // Acks for what the last client had in flight will never come
+            self.flow.reset();
+        }
+    }
+}
+
+/// Session server state, shared by every connection
+#[derive(Default)]
+struct Daemon {
+    sessions: Mutex<HashMap<String, DaemonSession>>,
+    connections: AtomicUsize,
+    next_connection_id: AtomicU64,
+}
+
+impl Daemon {
+    fn is_idle(&self) -> bool {
+        self.connections.load(Ordering::SeqCst) == 0 && self.sessions.lock().unwrap().is_empty()
+    }
+
+    fn session<T>(
+        &self,
+        id: &str,
+        f: impl FnOnce(&mut DaemonSession) -> T,
+    ) -> Result<T, String> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get_mut(id)
+            .ok_or_else(|| "Session not found".to_string())?;
+        Ok(f(session))
+    }
+
+    /// Queue a paste, see `paste::paste`
+    fn paste(
+        &self,
+        id: &str,
+        text: &str,
+        confirmed: bool,
+    ) -> Result<impl Future<Output = Result<DaemonReply, String>> + Send + 'static, String> {
+        let (writer, session) = self.session(id, |s| (s.writer.clone(), s.session.clone()))?;
+        let paste = paste::paste(&session, &writer, text, confirmed);
+        Ok(async move {
+            let result = paste.await.map_err(|e| format!("Paste failed: {}", e))?;
+            Ok(DaemonReply::Pasted { result })
+        })
+    }
+
+    /// Queue input for a session, `input` records and encodes it under the session lock
+    fn write(
+        &self,
+        id: &str,
+        input: impl FnOnce(&TerminalSession) -> Vec<u8>,
+    ) -> Result<impl Future<Output = Result<DaemonReply, String>> + Send + 'static, String> {
+        let (writer, session) = self.session(id, |s| (s.writer.clone(), s.session.clone()))?;
+        let data = input(&lock_session(&session));
+        let write = writer.write(data);
+        Ok(async move {
+            write.await.map_err(|e| format!("Write failed: {}", e))?;
+            Ok(DaemonReply::Done)
+        })
+    }
+
+    /// Handle for process lookups, which scan every process and so run without the session lock
+    fn processes(&self, id: &str) -> Result<SessionProcesses, String> {
+        let session = self.session(id, |s| s.session.clone())?;
+        let processes = lock_session(&session).processes();
+        Ok(processes)
+    }
+
+    /// Handle every request except `Close`, input, `ProcessTree` and `Signal`, which block
+    /// or wait for the PTY and run on their own tasks
+    fn handle_request(
+        self: &Arc<Self>,
+        connection_id: u64,
+        frames: &mpsc::UnboundedSender<ServerFrame>,
+        request: DaemonRequest,
+    ) -> Result<DaemonReply, String> {
+        match request {
+            DaemonRequest::Create { shell, spec } => {
+                let (mut session, writer, pid) = TerminalSession::new(shell, &spec)
+                    .map_err(|e| format!("Failed to create terminal: {}", e))?;
+                let shell = session.shell().to_string();
+                let output_rx = session
+                    .start_reader()
+                    .map_err(|e| format!("Failed to start terminal reader: {}", e))?;
+                let shell_events = session.take_shell_events();
+                let trigger_events = session.take_trigger_events();
+
+                let id = uuid::Uuid::new_v4().to_string();
+                let flow = session.output_flow();
+                let session = Arc::new(Mutex::new(session));
+                let mut entry = DaemonSession {
+                    session: session.clone(),
+                    writer,
+                    shell,
+                    pid,
+                    subscribers: HashMap::new(),
+                    flow,
+                };
+                entry.subscribers.insert(
+                    connection_id,
+                    Subscriber {
+                        frames: frames.clone(),
+                        start_offset: 0,
+                    },
+                );
+                let info = entry.info(&id);
+                self.sessions.lock().unwrap().insert(id.clone(), entry);
+
+                if let Some(events) = shell_events {
+                    let to_event = |id, event| DaemonEvent::Shell { id, event };
+                    tokio::spawn(forward_events(self.clone(), id.clone(), events, to_event));
+                }
+                if let Some(events) = trigger_events {
+                    let to_event = |id, event| DaemonEvent::Trigger { id, event };
+                    tokio::spawn(forward_events(self.clone(), id.clone(), events, to_event));
+                }
+                let process_changes = watch_foreground_process(&session);
+                let to_event = |id, info| DaemonEvent::Process { id, info };
+                tokio::spawn(forward_events(self.clone(), id.clone(), process_changes, to_event));
+                tokio::spawn(forward_output(self.clone(), id, session, output_rx));
+                Ok(DaemonReply::Created { session: info })
+            }
+            DaemonRequest::ProcessInfo { id } => {
+                let session = self.session(&id, |s| s.session.clone())?;
+                let info = lock_session(&session).get_process_info();
+                Ok(DaemonReply::Process { info })
+            }
+            DaemonRequest::Metrics { id } => {
+                let session = self.session(&id, |s| s.session.clone())?;
+                let metrics = lock_session(&session).metrics().snapshot();
+                Ok(DaemonReply::Metrics { metrics })
+            }
+            DaemonRequest::SetTriggers { id, triggers } => {
+                let session = self.session(&id, |s| s.session.clone())?;
+                lock_session(&session).set_triggers(&triggers)?;
+                Ok(DaemonReply::Done)
+            }
+            DaemonRequest::AckOutput { id, bytes } => {
+                self.session(&id, |s| s.flow.ack(bytes))?;
+                Ok(DaemonReply::Done)
+            }
+            DaemonRequest::ResetOutputWindow { id } => {
+                self.session(&id, |s| s.flow.reset())?;
+                Ok(DaemonReply::Done)
+            }
+            DaemonRequest::SetInputEncoding { id, encoding } => {
+                let session = self.session(&id, |s| s.session.clone())?;
+                lock_session(&session)
+                    .set_input_encoding(&encoding)
+                    .map_err(|e| e.to_string())?;
+                Ok(DaemonReply::Done)
+            }
+            DaemonRequest::Resize { id, cols, rows } => {
+                let session = self.session(&id, |s| s.session.clone())?;
+                lock_session(&session)
+                    .resize(cols as usize, rows as usize)
+                    .map_err(|e| format!("Resize failed: {}", e))?;
+                Ok(DaemonReply::Done)
+            }
+            DaemonRequest::List => {
+                let sessions = self.sessions.lock().unwrap();
+                Ok(DaemonReply::Sessions {
+                    sessions: sessions.iter().map(|(id, s)| s.info(id)).collect(),
+                })
+            }
+            DaemonRequest::Attach { id } => {
+                // Snapshot and subscribe under the sessions lock so no chunk is lost or doubled
+                let snapshot = self.session(&id, |s| {
+                    let snapshot = lock_session(&s.session).snapshot();
+                    s.subscribers.insert(
+                        connection_id,
+                        Subscriber {
+                            frames: frames.clone(),
+                            start_offset: snapshot.total_bytes,
+                        },
+                    );
+                    // Output sent to an earlier client won't be acked by this one
+                    s.flow.reset();
+                    snapshot
+                })?;
+                Ok(DaemonReply::Snapshot { snapshot })
+            }
+            DaemonRequest::Detach { id } => {
+                self.session(&id, |s| s.unsubscribe(connection_id))?;
+                // Follows the last output sent to this connection, so the client knows
+                // when it can let go of the session
+                let _ = frames.send(ServerFrame::Event {
+                    event: DaemonEvent::Detached { id },
+                });
+                Ok(DaemonReply::Done)
+            }
+            DaemonRequest::Snapshot { id } => {
+                let snapshot = self.session(&id, |s| lock_session(&s.session).snapshot())?;
+                Ok(DaemonReply::Snapshot { snapshot })
+            }
+            DaemonRequest::Screen { id, include_scrollback } => {
+                let screen = self.session(&id, |s| {
+                    lock_session(&s.session).screen_snapshot(include_scrollback)
+                })?;
+                Ok(DaemonReply::Screen { screen })
+            }
+            DaemonRequest::StartRecording { id, path } => {
+                let session = self.session(&id, |s| s.session.clone())?;
+                lock_session(&session)
+                    .start_recording(Path::new(&path))
+                    .map_err(|e| format!("Failed to start recording: {}", e))?;
+                Ok(DaemonReply::Done)
+            }
+            DaemonRequest::StopRecording { id } => {
+                let session = self.session(&id, |s| s.session.clone())?;
+                let info = lock_session(&session)
+                    .stop_recording()
+                    .map_err(|e| format!("Failed to stop recording: {}", e))?;
+                Ok(DaemonReply::Recording { info })
+            }
+            DaemonRequest::StartLog { id, path, options } => {
+                let session = self.session(&id, |s| s.session.clone())?;
+                lock_session(&session)
+                    .start_log(Path::new(&path), options)
+                    .map_err(|e| format!("Failed to start log: {}", e))?;
+                Ok(DaemonReply::Done)
+            }
+            DaemonRequest::StopLog { id } => {
+                let session = self.session(&id, |s| s.session.clone())?;
+                lock_session(&session)
+                    .stop_log()
+                    .map_err(|e| format!("Failed to stop log: {}", e))?;
+                Ok(DaemonReply::Done)
+            }
+            DaemonRequest::Close { .. }
+            | DaemonRequest::Write { .. }
+            | DaemonRequest::WriteText { .. }
+            | DaemonRequest::Paste { .. }
+            | DaemonRequest::ProcessTree { .. }
+            | DaemonRequest::Signal { .. } => {
+                Err("Request must be handled asynchronously".to_string())
+            }
+        }
+    }
+
+    async fn close_session(&self, id: &str, grace_period: Duration) -> Result<DaemonReply, String> {
+        let entry = self
+            .sessions
+            .lock()
+            .unwrap()
+            .remove(id)
+            .ok_or_else(|| "Session not found".to_string())?;
+
+        let session = entry.session;
+        // Terminating takes up to twice the grace period, don't hold the session meanwhile
+        let closer = lock_session(&session).closer();
+        let result = tokio::task::spawn_blocking(move || closer.terminate(grace_period))
+            .await
+            .map_err(|e| format!("Close failed: {}", e))?;
+        Ok(DaemonReply::Closed { result })
+    }
+
+    /// Drop a disconnected client from every session it was attached to
+    fn detach_all(&self, connection_id: u64) {
+        let mut sessions = self.sessions.lock().unwrap();
+        for session in sessions.values_mut() {
+            session.unsubscribe(connection_id);
+        }
+    }
+}
+
+/// Send a session's shell integration, trigger or process events to its attached clients
+async fn forward_events<T: Clone>(
+    daemon: Arc<Daemon>,
+    id: String,
+    mut events: mpsc::UnboundedReceiver<T>,
+    to_event: fn(String, T) -> DaemonEvent,
+) {
+    while let Some(event) = events.recv().await {
+        let sessions = daemon.sessions.lock().unwrap();
+        let Some(entry) = sessions.get(&id) else {
+            continue;
+        };
+        for subscriber in entry.subscribers.values() {
+            let _ = subscriber.frames.send(ServerFrame::Event {
+                event: to_event(id.clone(), event.clone()),
+            });
+        }
+    }
+}
+
+/// Fan a session's output out to its subscribers, then report its exit
+/// While clients are attached, output is held back once they are a window behind on acks.
+async fn forward_output(
+    daemon: Arc<Daemon>,
+    id: String,
+    session: Arc<Mutex<TerminalSession>>,
+    mut output_rx: PtyOutputReceiver,
+) {
+    let (flow, metrics) = {
+        let session = lock_session(&session);
+        (session.output_flow(), session.metrics())
+    };
+    let mut offset: u64 = 0;
+    while let Some(data) = output_rx.recv().await {
+        let attached = daemon
+            .sessions
+            .lock()
+            .unwrap()
+            .get(&id)
+            .is_some_and(|entry| !entry.subscribers.is_empty());
+        if attached {
+            flow.wait_for_window().await;
+        }
+
+        offset += data.len() as u64;
+        let sessions = daemon.sessions.lock().unwrap();
+        let Some(entry) = sessions.get(&id) else {
+            // Closed, keep draining until the reader thread stops
+            continue;
+        };
+        let mut sent = false;
+        for subscriber in entry.subscribers.values() {
+            if offset <= subscriber.start_offset {
+                continue;
+            }
+            let _ = subscriber.frames.send(ServerFrame::Event {
+                event: DaemonEvent::Output {
+                    id: id.clone(),
+                    data: data.clone(),
+                },
+            });
+            sent = true;
+        }
+        if sent {
+            flow.sent(data.len() as u64);
+            metrics.record_emit(data.len());
+        }
+    }
+
+    let status = loop {
+        let status = lock_session(&session).try_wait();
+        if let Some(status) = status {
+            break status;
+        }
+        tokio::time::sleep(EXIT_POLL_INTERVAL).await;
+    };
+
+    let recording = lock_session(&session).stop_recording();
+    let recording = recording.unwrap_or_else(|e| {
+        eprintln!("Failed to finish recording of session {}: {}", id, e);
+        None
+    });
+
+    let entry = daemon.sessions.lock().unwrap().remove(&id);
+    if let Some(entry) = entry {
+        for subscriber in entry.subscribers.values() {
+            if let Some(info) = &recording {
+                let _ = subscriber.frames.send(ServerFrame::Event {
+                    event: DaemonEvent::RecordingFinished {
+                        id: id.clone(),
+                        info: info.clone(),
+                    },
+                });
+            }
+            let _ = subscriber.frames.send(ServerFrame::Event {
+                event: DaemonEvent::Exit {
+                    id: id.clone(),
+                    status: status.clone(),
+                },
+            });
+        }
+    }
+}
+
+/// Run the session daemon on its own runtime until it is idle
+/// The daemon exits once it has no sessions and no connected clients.
+pub fn run(socket_path: &Path) -> std::io::Result<()> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(serve(socket_path))
+}
+
+#[cfg(unix)]
+async fn serve(socket_path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::net::{UnixListener, UnixStream};
+
+    if let Some(dir) = socket_path.parent() {
+        super::ensure_private_dir(dir)?;
+    }
+    if socket_path.exists() {
+        if UnixStream::connect(socket_path).await.is_ok() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AddrInUse,
+                "A session daemon is already running",
+            ));
+        }
+        // Left behind by a daemon that crashed
+        std::fs::remove_file(socket_path)?;
+    }
+
+    let listener = UnixListener::bind(socket_path)?;
+    std::fs::set_permissions(socket_path, std::fs::Permissions::from_mode(0o600))?;
+    eprintln!("Session daemon listening on {}", socket_path.display());
+
+    let daemon = Arc::new(Daemon::default());
+    let mut idle_checks = tokio::time::interval(IDLE_CHECK_INTERVAL);
+    // The first tick fires immediately, skip it so a new daemon waits for its first client
+    idle_checks.tick().await;
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => match check_peer(&stream) {
+                    Ok(()) => {
+                        tokio::spawn(handle_connection(daemon.clone(), stream));
+                    }
+                    Err(e) => eprintln!("Session daemon rejected a connection: {}", e),
+                },
+                Err(e) => eprintln!("Session daemon accept failed: {}", e),
+            },
+            _ = idle_checks.tick() => {
+                if daemon.is_idle() {
+                    break;
+                }
+            }
+        }
+    }
+
+    eprintln!("Session daemon idle, shutting down");
+    let _ = std::fs::remove_file(socket_path);
+    Ok(())
+}
+
+/// Refuse a peer that runs as another user, with SO_PEERCRED on Linux and getpeereid on macOS
+#[cfg(unix)]
+fn check_peer(stream: &tokio::net::UnixStream) -> std::io::Result<()> {
+    let peer = stream.peer_cred()?;
+    let uid = unsafe { libc::getuid() };
+    if peer.uid() != uid {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!("Peer runs as uid {}, not as the current user", peer.uid()),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn serve(_socket_path: &Path) -> std::io::Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(unix)]
+async fn handle_connection(daemon: Arc<Daemon>, stream: tokio::net::UnixStream) {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let connection_id = daemon.next_connection_id.fetch_add(1, Ordering::SeqCst);
+    daemon.connections.fetch_add(1, Ordering::SeqCst);
+
+    let (read_half, mut write_half) = stream.into_split();
+    let (frames_tx, mut frames_rx) = mpsc::unbounded_channel::<ServerFrame>();
+
+    // Replies and events share one writer so lines never interleave
+    let writer_task = tokio::spawn(async move {
+        while let Some(frame) = frames_rx.recv().await {
+            let mut line = match serde_json::to_vec(&frame) {
+                Ok(line) => line,
+                Err(e) => {
+                    eprintln!("Failed to serialize daemon frame: {}", e);
+                    continue;
+                }
+            };
+            line.push(b'\n');
+            if write_half.write_all(&line).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut lines = BufReader::new(read_half).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let frame: RequestFrame = match serde_json::from_str(&line) {
+            Ok(frame) => frame,
+            Err(e) => {
+                eprintln!("Invalid daemon request: {}", e);
+                continue;
+            }
+        };
+
+        let req_id = frame.req_id;
+        match frame.request {
+            DaemonRequest::Close { id, grace_period_ms } => {
+                // Closing can take a while, don't hold up other requests on this connection
+                let daemon = daemon.clone();
+                let frames = frames_tx.clone();
+                tokio::spawn(async move {
+                    let result = daemon
+                        .close_session(&id, Duration::from_millis(grace_period_ms))
+                        .await;
+                    let _ = frames.send(ServerFrame::Reply { req_id, result });
+                });
+            }
+            // Input is queued here so it is written in the order it arrived, the reply waits
+            // for the write on its own task since large pastes are throttled
+            DaemonRequest::Write { id, data } => {
+                let write = daemon.write(&id, move |session| {
+                    session.record_input(&data);
+                    data
+                });
+                reply_when_done(&frames_tx, req_id, write);
+            }
+            DaemonRequest::WriteText { id, text } => {
+                let write = daemon.write(&id, |session| {
+                    session.record_input(text.as_bytes());
+                    session.encode_input(&text).into_owned()
+                });
+                reply_when_done(&frames_tx, req_id, write);
+            }
+            DaemonRequest::Paste { id, text, confirmed } => {
+                reply_when_done(&frames_tx, req_id, daemon.paste(&id, &text, confirmed));
+            }
+            DaemonRequest::ProcessTree { id } => {
+                let processes = daemon.processes(&id);
+                let frames = frames_tx.clone();
+                tokio::task::spawn_blocking(move || {
+                    let result = processes.map(|processes| DaemonReply::ProcessTree {
+                        tree: processes.tree(),
+                    });
+                    let _ = frames.send(ServerFrame::Reply { req_id, result });
+                });
+            }
+            DaemonRequest::Signal { id, signal, pid } => {
+                // Checking `pid` against the process tree scans every process
+                let processes = daemon.processes(&id);
+                let frames = frames_tx.clone();
+                tokio::task::spawn_blocking(move || {
+                    let result = processes.and_then(|processes| {
+                        let target = processes
+                            .signal(signal, pid)
+                            .map_err(|e| format!("Signal failed: {}", e))?;
+                        Ok(DaemonReply::Signalled { target })
+                    });
+                    let _ = frames.send(ServerFrame::Reply { req_id, result });
+                });
+            }
+            request => {
+                let result = daemon.handle_request(connection_id, &frames_tx, request);
+                let _ = frames_tx.send(ServerFrame::Reply { req_id, result });
+            }
+        }
+    }
+
+    daemon.detach_all(connection_id);
+    daemon.connections.fetch_sub(1, Ordering::SeqCst);
+    drop(frames_tx);
+    let _ = writer_task.await;
+}
+
+/// Send the reply of a queued request once it is done, without holding up the connection
+fn reply_when_done(
+    frames: &mpsc::UnboundedSender<ServerFrame>,
+    req_id: u64,
+    queued: Result<impl Future<Output = Result<DaemonReply, String>> + Send + 'static, String>,
+) {
+    let frames = frames.clone();
+    tokio::spawn(async move {
+        let result = match queued {
+            Ok(done) => done.await,
+            Err(e) => Err(e),
+        };
+        let _ = frames.send(ServerFrame::Reply { req_id, result });
+    });
+}
+
+type PendingReplies = Arc<Mutex<HashMap<u64, oneshot::Sender<Result<DaemonReply, String>>>>>;
+
+/// Connection from the app to the session daemon
+/// Requests are matched to replies by ID, events are forwarded to the channel given to `connect`.
+pub struct DaemonClient {
+    frames: mpsc::UnboundedSender<Vec<u8>>,
+    pending: PendingReplies,
+    next_req_id: AtomicU64,
+}
+
+impl DaemonClient {
+    /// Connect to a running daemon
+    #[cfg(unix)]
+    pub async fn connect(
+        socket_path: &Path,
+        events: mpsc::UnboundedSender<DaemonEvent>,
+    ) -> std::io::Result<Self> {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+        use tokio::net::UnixStream;
+
+        // Whoever could put a socket here would get every keystroke, so check both the
+        // directory and the process listening on it
+        if let Some(dir) = socket_path.parent() {
+            super::ensure_private_dir(dir)?;
+        }
+        let stream = UnixStream::connect(socket_path).await?;
+        check_peer(&stream)?;
+        let (read_half, mut write_half) = stream.into_split();
+        let (frames_tx, mut frames_rx) = mpsc::unbounded_channel::<Vec<u8>>();
+        let pending: PendingReplies = Arc::new(Mutex::new(HashMap::new()));
+
+        tokio::spawn(async move {
+            while let Some(line) = frames_rx.recv().await {
+                if write_half.write_all(&line).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let pending_clone = pending.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(read_half).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                match serde_json::from_str::<ServerFrame>(&line) {
+                    Ok(ServerFrame::Reply { req_id, result }) => {
+                        let waiter = pending_clone.lock().unwrap().remove(&req_id);
+                        if let Some(waiter) = waiter {
+                            let _ = waiter.send(result);
+                        }
+                    }
+                    Ok(ServerFrame::Event { event }) => {
+                        let _ = events.send(event);
+                    }
+                    Err(e) => eprintln!("Invalid daemon frame: {}", e),
+                }
+            }
+            // Dropping the waiters fails every request still in flight
+            pending_clone.lock().unwrap().clear();
+            eprintln!("Session daemon connection closed");
+        });
+
+        Ok(Self {
+            frames: frames_tx,
+            pending,
+            next_req_id: AtomicU64::new(1),
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub async fn connect(
+        _socket_path: &Path,
+        _events: mpsc::UnboundedSender<DaemonEvent>,
+    ) -> std::io::Result<Self> {
+        Err(unsupported())
+    }
+
+    /// Connect to the daemon, spawning it first if nothing is listening on the socket
+    pub async fn connect_or_spawn(
+        socket_path: &Path,
+        events: mpsc::UnboundedSender<DaemonEvent>,
+    ) -> std::io::Result<Self> {
+        if let Ok(client) = Self::connect(socket_path, events.clone()).await {
+            return Ok(client);
+        }
+
+        spawn_daemon(socket_path)?;
+        let deadline = tokio::time::Instant::now() + SPAWN_CONNECT_TIMEOUT;
+        loop {
+            match Self::connect(socket_path, events.clone()).await {
+                Ok(client) => return Ok(client),
+                Err(e) if tokio::time::Instant::now() >= deadline => return Err(e),
+                Err(_) => tokio::time::sleep(SPAWN_CONNECT_RETRY).await,
+            }
+        }
+    }
+
+    /// Send a request and wait for its reply
+    pub async fn request(&self, request: DaemonRequest) -> Result<DaemonReply, String> {
+        let req_id = self.next_req_id.fetch_add(1, Ordering::SeqCst);
+        let mut line = serde_json::to_vec(&RequestFrame { req_id, request })
+            .map_err(|e| format!("Failed to serialize daemon request: {}", e))?;
+        line.push(b'\n');
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(req_id, reply_tx);
+        if self.frames.send(line).is_err() {
+            self.pending.lock().unwrap().remove(&req_id);
+            return Err("Session daemon disconnected".to_string());
+        }
+
+        reply_rx
+            .await
+            .map_err(|_| "Session daemon disconnected".to_string())?
+    }
+}
+
+/// Start the daemon as a detached copy of the current executable
+/// It gets its own session so it outlives the app and isn't hung up with it.
+#[cfg(unix)]
+fn spawn_daemon(socket_path: &Path) -> std::io::Result<()> {
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    let exe = std::env::current_exe()?;
+    let mut command = Command::new(exe);
+    command
+        .arg(DAEMON_FLAG)
+        .arg(socket_path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let mut child = command.spawn()?;
+    // Reap it when it eventually exits so it doesn't linger as a zombie
+    std::thread::spawn(move || {
+        let _ = child.wait();
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn spawn_daemon(_socket_path: &Path) -> std::io::Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_is_sent_as_base64() {
+        let event = DaemonEvent::Output {
+            id: "1".to_string(),
+            data: b"\x1b[1mhi\xff\r\n".to_vec(),
+        };
+        let line = serde_json::to_string(&event).unwrap();
+        assert_eq!(line, r#"{"type":"output","id":"1","data":"G1sxbWhp/w0K"}"#);
+
+        let DaemonEvent::Output { data, .. } = serde_json::from_str(&line).unwrap() else {
+            panic!("Expected output, got {}", line);
+        };
+        assert_eq!(data, b"\x1b[1mhi\xff\r\n");
+    }
+}
@@ -0,0 +1,55 @@
+use std::borrow::Cow;
+
+use encoding_rs::{EncoderResult, Encoding, UTF_8};
+
+/// Character encoding applied to text written to a session
+/// Sessions default to UTF-8; hosts that expect e.g. Shift_JIS or windows-1252 can be
+/// given the matching WHATWG label so typed text arrives in their encoding.
+#[derive(Debug, Clone, Copy)]
+pub struct InputEncoding(&'static Encoding);
+
+impl Default for InputEncoding {
+    fn default() -> Self {
+        Self(UTF_8)
+    }
+}
+
+impl InputEncoding {
+    /// Look up an encoding by label, e.g. "utf-8", "shift_jis", "euc-kr", "windows-1252"
+    pub fn for_label(label: &str) -> Option<Self> {
+        Encoding::for_label(label.trim().as_bytes()).map(Self)
+    }
+
+    /// Canonical name of the encoding
+    pub fn name(&self) -> &'static str {
+        self.0.name()
+    }
+
+    /// Encode text, characters the encoding can't represent become `?`
+    pub fn encode<'a>(&self, text: &'a str) -> Cow<'a, [u8]> {
+        // UTF-16 labels resolve to UTF-8 for output, like in browsers
+        let encoding = self.0.output_encoding();
+        if encoding == UTF_8 {
+            return Cow::Borrowed(text.as_bytes());
+        }
+
+        let mut encoder = encoding.new_encoder();
+        let mut out = Vec::with_capacity(
+            encoder
+                .max_buffer_length_from_utf8_without_replacement(text.len())
+                .unwrap_or(text.len()),
+        );
+        let mut remaining = text;
+        loop {
+            let (result, read) =
+                encoder.encode_from_utf8_to_vec_without_replacement(remaining, &mut out, true);
+            remaining = &remaining[read..];
+            match result {
+                EncoderResult::InputEmpty => break,
+                EncoderResult::OutputFull => out.reserve(remaining.len() * 4 + 16),
+                EncoderResult::Unmappable(_) => out.push(b'?'),
+            }
+        }
+        Cow::Owned(out)
+    }
+}
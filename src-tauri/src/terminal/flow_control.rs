@@ -0,0 +1,144 @@
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::Notify;
+
+/// Bytes the frontend may have in flight before the session stops reading its PTY
+/// The reader thread keeps queueing until its channel is full, then the kernel's PTY
+/// buffer fills and the program writing the output blocks.
+pub const OUTPUT_WINDOW: u64 = 1024 * 1024;
+
+/// Windowed flow control between a session's output and the frontend rendering it
+/// Output is counted with `sent` as it is emitted and released with `ack` once the
+/// frontend has rendered it. While too much is unacked, the app's frame loop leaves the
+/// session out until `watch` tells it the window reopened, and the daemon's output loop
+/// holds in `wait_for_window`.
+#[derive(Debug)]
+pub struct FlowControl {
+    window: u64,
+    state: Mutex<FlowState>,
+    acked: Notify,
+    /// Set with `watch`, notified when the window opens again
+    watcher: Mutex<Option<Arc<Notify>>>,
+}
+
+#[derive(Debug, Default)]
+struct FlowState {
+    unacked: u64,
+    /// Set once nothing will ack the output anymore, the window stays open from then on
+    disabled: bool,
+}
+
+impl Default for FlowControl {
+    fn default() -> Self {
+        Self::new(OUTPUT_WINDOW)
+    }
+}
+
+impl FlowControl {
+    pub fn new(window: u64) -> Self {
+        Self {
+            window,
+            state: Mutex::new(FlowState::default()),
+            acked: Notify::new(),
+            watcher: Mutex::new(None),
+        }
+    }
+
+    /// Notify `watcher` whenever acks reopen the window, replacing an earlier watcher
+    /// A window that reopens before the watcher waits leaves it a permit, so it isn't missed.
+    pub fn watch(&self, watcher: Arc<Notify>) {
+        *self.watcher.lock().unwrap() = Some(watcher);
+    }
+
+    /// Bytes emitted but not acknowledged yet
+    pub fn unacked(&self) -> u64 {
+        self.state.lock().unwrap().unacked
+    }
+
+    /// True while more output may be sent
+    pub fn is_open(&self) -> bool {
+        self.open(&self.state.lock().unwrap())
+    }
+
+    fn open(&self, state: &FlowState) -> bool {
+        state.disabled || state.unacked < self.window
+    }
+
+    /// Wait until the unacked bytes are below the window
+    pub async fn wait_for_window(&self) {
+        loop {
+            // Registered before checking so an ack in between isn't missed
+            let acked = self.acked.notified();
+            if self.is_open() {
+                return;
+            }
+            acked.await;
+        }
+    }
+
+    /// Count bytes handed to the frontend
+    pub fn sent(&self, bytes: u64) {
+        self.state.lock().unwrap().unacked += bytes;
+    }
+
+    /// Release bytes the frontend has rendered
+    /// Acks for more than is outstanding, e.g. after `reset`, are clamped.
+    pub fn ack(&self, bytes: u64) {
+        self.update(|state| state.unacked = state.unacked.saturating_sub(bytes));
+    }
+
+    /// Forget everything outstanding, e.g. when the frontend reloaded and its acks will never come
+    pub fn reset(&self) {
+        self.update(|state| state.unacked = 0);
+    }
+
+    /// Open the window for good, e.g. when the session is closed and its output only needs draining
+    pub fn disable(&self) {
+        self.update(|state| state.disabled = true);
+    }
+
+    /// Apply a change that may open the window and wake whoever waits for it
+    fn update(&self, change: impl FnOnce(&mut FlowState)) {
+        let reopened = {
+            let mut state = self.state.lock().unwrap();
+            let was_open = self.open(&state);
+            change(&mut state);
+            !was_open && self.open(&state)
+        };
+        self.acked.notify_waiters();
+        if reopened {
+            if let Some(watcher) = self.watcher.lock().unwrap().as_ref() {
+                watcher.notify_one();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watcher_is_notified_when_the_window_reopens() {
+        let flow = FlowControl::new(100);
+        let watcher = Arc::new(Notify::new());
+        flow.watch(watcher.clone());
+        let notified = || {
+            let notified = watcher.notified();
+            tokio::pin!(notified);
+            notified.enable()
+        };
+
+        flow.sent(150);
+        flow.ack(20);
+        assert!(!flow.is_open());
+        assert!(!notified(), "Still closed, nothing to wake");
+
+        flow.ack(40);
+        assert!(flow.is_open());
+        assert!(notified());
+
+        flow.ack(40);
+        assert!(!notified(), "Was open already");
+    }
+}
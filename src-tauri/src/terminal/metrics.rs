@@ -0,0 +1,120 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Throughput and latency counters of a session, updated from the reader thread,
+/// the output loop and every lock of the session
+#[derive(Debug)]
+pub struct SessionMetrics {
+    started: Instant,
+    bytes_read: AtomicU64,
+    bytes_emitted: AtomicU64,
+    emit_count: AtomicU64,
+    max_batch_bytes: AtomicU64,
+    lock_wait_ns: AtomicU64,
+    max_lock_wait_ns: AtomicU64,
+    echo: Mutex<EchoLatency>,
+}
+
+#[derive(Debug, Default)]
+struct EchoLatency {
+    /// When input was written that hasn't been followed by output yet
+    pending_input: Option<Instant>,
+    last: Option<Duration>,
+    max: Duration,
+    total: Duration,
+    samples: u64,
+}
+
+/// Counters of a session as returned by `get_session_metrics`, times in microseconds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionMetricsSnapshot {
+    pub uptime_ms: u64,
+    /// Read from the PTY
+    pub bytes_read: u64,
+    /// Handed to the frontend, or to attached clients in the daemon
+    pub bytes_emitted: u64,
+    pub emit_count: u64,
+    pub max_batch_bytes: u64,
+    /// Total time spent waiting to lock the session
+    pub lock_wait_us: u64,
+    pub max_lock_wait_us: u64,
+    /// From input being written to the next output, usually the echo
+    pub last_echo_latency_us: Option<u64>,
+    pub avg_echo_latency_us: Option<u64>,
+    pub max_echo_latency_us: Option<u64>,
+    pub echo_samples: u64,
+}
+
+impl Default for SessionMetrics {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            bytes_read: AtomicU64::new(0),
+            bytes_emitted: AtomicU64::new(0),
+            emit_count: AtomicU64::new(0),
+            max_batch_bytes: AtomicU64::new(0),
+            lock_wait_ns: AtomicU64::new(0),
+            max_lock_wait_ns: AtomicU64::new(0),
+            echo: Mutex::new(EchoLatency::default()),
+        }
+    }
+}
+
+impl SessionMetrics {
+    /// Count output read from the PTY, completing an echo measurement if input is pending
+    pub fn record_read(&self, bytes: usize) {
+        self.bytes_read.fetch_add(bytes as u64, Ordering::Relaxed);
+        let mut echo = self.echo.lock().unwrap();
+        if let Some(input) = echo.pending_input.take() {
+            let latency = input.elapsed();
+            echo.last = Some(latency);
+            echo.max = echo.max.max(latency);
+            echo.total += latency;
+            echo.samples += 1;
+        }
+    }
+
+    /// Start an echo measurement, unless one is already waiting for output
+    pub fn record_input(&self) {
+        let mut echo = self.echo.lock().unwrap();
+        if echo.pending_input.is_none() {
+            echo.pending_input = Some(Instant::now());
+        }
+    }
+
+    /// Count one batch of output handed on
+    pub fn record_emit(&self, bytes: usize) {
+        self.bytes_emitted.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.emit_count.fetch_add(1, Ordering::Relaxed);
+        self.max_batch_bytes.fetch_max(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_lock_wait(&self, wait: Duration) {
+        let wait = wait.as_nanos() as u64;
+        self.lock_wait_ns.fetch_add(wait, Ordering::Relaxed);
+        self.max_lock_wait_ns.fetch_max(wait, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> SessionMetricsSnapshot {
+        let echo = self.echo.lock().unwrap();
+        let micros = |duration: Duration| duration.as_micros() as u64;
+        SessionMetricsSnapshot {
+            uptime_ms: self.started.elapsed().as_millis() as u64,
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_emitted: self.bytes_emitted.load(Ordering::Relaxed),
+            emit_count: self.emit_count.load(Ordering::Relaxed),
+            max_batch_bytes: self.max_batch_bytes.load(Ordering::Relaxed),
+            lock_wait_us: self.lock_wait_ns.load(Ordering::Relaxed) / 1000,
+            max_lock_wait_us: self.max_lock_wait_ns.load(Ordering::Relaxed) / 1000,
+            last_echo_latency_us: echo.last.map(micros),
+            avg_echo_latency_us: (echo.samples > 0)
+                .then(|| micros(echo.total) / echo.samples),
+            max_echo_latency_us: (echo.samples > 0).then(|| micros(echo.max)),
+            echo_samples: echo.samples,
+        }
+    }
+}
@@ -1,5 +1,122 @@
+use std::path::{Path, PathBuf};
+
+pub mod daemon;
+pub mod encoding;
+pub mod flow_control;
 pub mod history;
+pub mod metrics;
+pub mod paste;
+pub mod recording;
+pub mod screen;
+pub mod scrollback;
+pub mod session_log;
+pub mod shell_integration;
+pub mod shell_profile;
+pub mod spec;
+pub mod triggers;
 pub mod vte_parser;
+pub mod writer;
 
+pub use screen::{ScreenSnapshot, TerminalScreen};
+pub use flow_control::FlowControl;
+pub use metrics::{SessionMetrics, SessionMetricsSnapshot};
+pub use paste::PasteResult;
+pub use recording::RecordingInfo;
+pub use scrollback::ScrollbackSnapshot;
+pub use session_log::{LogFormat, LogOptions};
+pub use shell_integration::ShellEvent;
+pub use shell_profile::ShellKind;
+pub use spec::{SessionKind, SessionSpec};
+pub use triggers::{TriggerAction, TriggerConfig, TriggerEvent};
 pub use vte_parser::TerminalSession;
-pub use vte_parser::PtyWriter;
+pub use writer::PtyWriter;
+pub use vte_parser::PtyOutputReceiver;
+pub use vte_parser::ShellEventReceiver;
+pub use vte_parser::TriggerEventReceiver;
+pub use vte_parser::ProcessChangeReceiver;
+pub use vte_parser::SessionExitStatus;
+pub use vte_parser::SessionCloseResult;
+pub use vte_parser::SessionCloser;
+pub use vte_parser::SessionProcesses;
+pub use vte_parser::SessionSignal;
+
+/// Write to a session's PTY and flush it, see `PtyWriter::write`
+pub async fn write_pty(writer: &PtyWriter, data: Vec<u8>) -> Result<(), String> {
+    writer
+        .write(data)
+        .await
+        .map_err(|e| format!("Write failed: {}", e))
+}
+
+/// Name of the app's data directory, the bundle identifier from tauri.conf.json
+const APP_IDENTIFIER: &str = "com.tipterm.app";
+
+/// Per-user directory for the daemon socket and generated shell integration files
+/// `$XDG_RUNTIME_DIR/tipterm` when set, otherwise a directory in the app's data dir.
+/// Anything in it is trusted (shells run the scripts), so it is refused unless only
+/// the current user can access it, see `ensure_private_dir`.
+pub fn runtime_dir() -> std::io::Result<PathBuf> {
+    let dir = match std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => dir.join("tipterm"),
+        _ => app_data_dir()?.join("run"),
+    };
+    ensure_private_dir(&dir)?;
+    Ok(dir)
+}
+
+/// Same location Tauri uses for `app_data_dir`
+fn app_data_dir() -> std::io::Result<PathBuf> {
+    let missing = |name: &str| {
+        std::io::Error::new(std::io::ErrorKind::NotFound, format!("{} is not set", name))
+    };
+    #[cfg(target_os = "macos")]
+    let base = std::env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join("Library").join("Application Support"))
+        .ok_or_else(|| missing("HOME"))?;
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let base = match std::env::var_os("XDG_DATA_HOME").map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => dir,
+        _ => std::env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".local").join("share"))
+            .ok_or_else(|| missing("HOME"))?,
+    };
+    #[cfg(not(unix))]
+    let base = std::env::var_os("LOCALAPPDATA")
+        .map(PathBuf::from)
+        .ok_or_else(|| missing("LOCALAPPDATA"))?;
+    Ok(base.join(APP_IDENTIFIER))
+}
+
+/// Create `dir` if needed and check that it is a real directory owned by the current
+/// user with mode 0700, so nobody else can plant or swap files in it
+#[cfg(unix)]
+pub fn ensure_private_dir(dir: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    builder.mode(0o700);
+    builder.create(dir)?;
+
+    let metadata = std::fs::symlink_metadata(dir)?;
+    let uid = unsafe { libc::getuid() };
+    if !metadata.is_dir() || metadata.uid() != uid {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!("{} is not a directory owned by the current user", dir.display()),
+        ));
+    }
+    if metadata.mode() & 0o777 != 0o700 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!("{} is accessible by other users, expected mode 0700", dir.display()),
+        ));
+    }
+    Ok(())
+}
+
+/// Create `dir` if needed, the per-user data dir is private on Windows
+#[cfg(not(unix))]
+pub fn ensure_private_dir(dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)
+}
@@ -0,0 +1,153 @@
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::vte_parser::{lock_session, TerminalSession};
+use super::writer::PtyWriter;
+
+/// Pastes larger than this are written in chunks so slow programs can keep up
+const PASTE_CHUNK_BYTES: usize = 4096;
+const PASTE_CHUNK_DELAY: Duration = Duration::from_millis(10);
+
+const BRACKETED_PASTE_START: &str = "\x1b[200~";
+const BRACKETED_PASTE_END: &str = "\x1b[201~";
+
+/// Why a paste should be confirmed before it is sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PasteWarning {
+    /// Each line break would run a command
+    MultiLine,
+    /// The text runs something with `sudo`
+    Sudo,
+}
+
+/// Result of `paste_to_session`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum PasteResult {
+    #[serde(rename_all = "camelCase")]
+    Pasted { bytes: usize, bracketed: bool },
+    /// Nothing was written, paste again with `confirmed` to send it anyway
+    #[serde(rename_all = "camelCase")]
+    NeedsConfirmation { warnings: Vec<PasteWarning>, lines: usize },
+}
+
+/// Warnings for a paste going to a shell without bracketed paste
+pub fn paste_warnings(text: &str) -> Vec<PasteWarning> {
+    let mut warnings = Vec::new();
+    if text.contains(['\n', '\r']) {
+        warnings.push(PasteWarning::MultiLine);
+    }
+    let is_separator = |c: char| c.is_whitespace() || matches!(c, ';' | '&' | '|' | '(' | ')' | '`');
+    if text.split(is_separator).any(|word| word == "sudo") {
+        warnings.push(PasteWarning::Sudo);
+    }
+    warnings
+}
+
+/// Text to send for a paste
+/// Line breaks become CR like a typed Enter. With bracketed paste the text is wrapped in
+/// the paste markers, and every ESC inside it is dropped so it can't end the paste early.
+pub fn paste_payload(text: &str, bracketed: bool) -> String {
+    let text = text.replace("\r\n", "\r").replace('\n', "\r");
+    if !bracketed {
+        return text;
+    }
+    // Removing only the markers could join the rest into a new one, e.g. "\x1b[20\x1b[201~1~"
+    let text = text.replace('\x1b', "");
+    format!("{}{}{}", BRACKETED_PASTE_START, text, BRACKETED_PASTE_END)
+}
+
+/// Paste `text` into a session
+/// Without bracketed paste, multi-line and `sudo` pastes are only sent when `confirmed`.
+/// The paste is queued before this returns, the future resolves once its last chunk is written.
+pub fn paste(
+    session: &Mutex<TerminalSession>,
+    writer: &PtyWriter,
+    text: &str,
+    confirmed: bool,
+) -> impl Future<Output = std::io::Result<PasteResult>> + Send + 'static {
+    let queued = prepare(session, text, confirmed).map(|(data, bracketed)| {
+        let bytes = data.len();
+        let write = writer.write_chunked(data, PASTE_CHUNK_BYTES, PASTE_CHUNK_DELAY);
+        (write, bytes, bracketed)
+    });
+    async move {
+        let (write, bytes, bracketed) = match queued {
+            Ok(queued) => queued,
+            Err(needs_confirmation) => return Ok(needs_confirmation),
+        };
+        write.await?;
+        Ok(PasteResult::Pasted { bytes, bracketed })
+    }
+}
+
+/// Bytes to write for a paste and whether it is bracketed, or why it needs confirmation
+fn prepare(
+    session: &Mutex<TerminalSession>,
+    text: &str,
+    confirmed: bool,
+) -> Result<(Vec<u8>, bool), PasteResult> {
+    let session = lock_session(session);
+    let bracketed = session.screen().lock().unwrap().modes().bracketed_paste;
+    if !bracketed && !confirmed {
+        let warnings = paste_warnings(text);
+        if !warnings.is_empty() {
+            return Err(PasteResult::NeedsConfirmation {
+                warnings,
+                lines: text.lines().count(),
+            });
+        }
+    }
+
+    let payload = paste_payload(text, bracketed);
+    session.record_input(payload.as_bytes());
+    Ok((session.encode_input(&payload).into_owned(), bracketed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_breaks_become_cr() {
+        assert_eq!(paste_payload("a\r\nb\nc", false), "a\rb\rc");
+    }
+
+    #[test]
+    fn unbracketed_payload_is_unchanged() {
+        assert_eq!(paste_payload("\x1b[201~ls", false), "\x1b[201~ls");
+    }
+
+    #[test]
+    fn bracketed_payload_is_wrapped() {
+        assert_eq!(paste_payload("ls\n", true), "\x1b[200~ls\r\x1b[201~");
+    }
+
+    #[test]
+    fn bracketed_payload_cannot_end_the_paste() {
+        for text in ["\x1b[201~rm -rf ~\n", "\x1b[20\x1b[201~1~rm -rf ~\n"] {
+            let payload = paste_payload(text, true);
+            let inner = payload
+                .strip_prefix(BRACKETED_PASTE_START)
+                .and_then(|payload| payload.strip_suffix(BRACKETED_PASTE_END))
+                .unwrap();
+            assert!(!inner.contains('\x1b'), "{:?}", payload);
+        }
+        assert_eq!(
+            paste_payload("\x1b[20\x1b[201~1~ls", true),
+            "\x1b[200~[20[201~1~ls\x1b[201~"
+        );
+    }
+
+    #[test]
+    fn warnings() {
+        assert!(paste_warnings("ls -la").is_empty());
+        assert_eq!(paste_warnings("ls\npwd"), vec![PasteWarning::MultiLine]);
+        assert_eq!(paste_warnings("cd /tmp;sudo rm x"), vec![PasteWarning::Sudo]);
+        assert!(paste_warnings("pseudocode").is_empty());
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/// Header line of an asciicast v2 file
+/// https://docs.asciinema.org/manual/asciicast/v2/
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CastHeader {
+    pub version: u32,
+    pub width: u16,
+    pub height: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+    /// Longest pause kept during playback, in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idle_time_limit: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<HashMap<String, String>>,
+}
+
+/// A single event line: `[time, code, data]`
+/// Codes are "o" for output, "i" for input and "r" for resize ("COLSxROWS").
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CastEvent(pub f64, pub String, pub String);
+
+impl CastEvent {
+    pub fn time(&self) -> f64 {
+        self.0
+    }
+
+    pub fn code(&self) -> &str {
+        &self.1
+    }
+
+    pub fn data(&self) -> &str {
+        &self.2
+    }
+
+    /// Size carried by a resize event
+    pub fn resize(&self) -> Option<(u16, u16)> {
+        if self.1 != "r" {
+            return None;
+        }
+        let (cols, rows) = self.2.split_once('x')?;
+        Some((cols.parse().ok()?, rows.parse().ok()?))
+    }
+}
+
+/// Summary returned by `stop_recording`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingInfo {
+    pub path: String,
+    pub duration_ms: u64,
+    pub events: u64,
+}
+
+/// Writes a session's output, input and resizes to an asciicast v2 file
+pub struct Recorder {
+    writer: BufWriter<File>,
+    path: PathBuf,
+    started: Instant,
+    events: u64,
+    /// Trailing bytes of an incomplete UTF-8 sequence, completed by the next chunk
+    output_pending: Vec<u8>,
+    input_pending: Vec<u8>,
+}
+
+impl Recorder {
+    /// Create the cast file and write its header
+    pub fn create(path: &Path, cols: u16, rows: u16, title: Option<&str>, shell: &str) -> std::io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        let mut env = HashMap::new();
+        env.insert("SHELL".to_string(), shell.to_string());
+        env.insert("TERM".to_string(), "xterm-256color".to_string());
+        let header = CastHeader {
+            version: 2,
+            width: cols,
+            height: rows,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .ok(),
+            idle_time_limit: None,
+            title: title.map(|t| t.to_string()),
+            env: Some(env),
+        };
+        serde_json::to_writer(&mut writer, &header)?;
+        writer.write_all(b"\n")?;
+
+        Ok(Self {
+            writer,
+            path: path.to_path_buf(),
+            started: Instant::now(),
+            events: 0,
+            output_pending: Vec::new(),
+            input_pending: Vec::new(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn write_event(&mut self, code: &str, data: String) -> std::io::Result<()> {
+        // Microsecond precision, like asciinema itself
+        let time = (self.started.elapsed().as_secs_f64() * 1e6).round() / 1e6;
+        serde_json::to_writer(&mut self.writer, &CastEvent(time, code.to_string(), data))?;
+        self.writer.write_all(b"\n")?;
+        self.events += 1;
+        Ok(())
+    }
+
+    pub fn record_output(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let text = decode_utf8(&mut self.output_pending, data);
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.write_event("o", text)
+    }
+
+    pub fn record_input(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let text = decode_utf8(&mut self.input_pending, data);
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.write_event("i", text)
+    }
+
+    pub fn record_resize(&mut self, cols: u16, rows: u16) -> std::io::Result<()> {
+        self.write_event("r", format!("{}x{}", cols, rows))
+    }
+
+    /// Flush the file and report what was recorded
+    pub fn finish(mut self) -> std::io::Result<RecordingInfo> {
+        // A sequence cut off by the end of the recording can't be completed anymore
+        if !self.output_pending.is_empty() {
+            let text = String::from_utf8_lossy(&std::mem::take(&mut self.output_pending)).to_string();
+            self.write_event("o", text)?;
+        }
+        if !self.input_pending.is_empty() {
+            let text = String::from_utf8_lossy(&std::mem::take(&mut self.input_pending)).to_string();
+            self.write_event("i", text)?;
+        }
+        self.writer.flush()?;
+
+        Ok(RecordingInfo {
+            path: self.path.to_string_lossy().to_string(),
+            duration_ms: self.started.elapsed().as_millis() as u64,
+            events: self.events,
+        })
+    }
+}
+
+/// Decode `data` appended to `pending`, keeping an incomplete trailing sequence in `pending`
+/// Invalid bytes become U+FFFD.
+fn decode_utf8(pending: &mut Vec<u8>, data: &[u8]) -> String {
+    pending.extend_from_slice(data);
+    let mut text = String::new();
+    loop {
+        match std::str::from_utf8(pending) {
+            Ok(valid) => {
+                text.push_str(valid);
+                pending.clear();
+                return text;
+            }
+            Err(e) => {
+                let valid_up_to = e.valid_up_to();
+                text.push_str(&String::from_utf8_lossy(&pending[..valid_up_to]));
+                match e.error_len() {
+                    Some(len) => {
+                        text.push('\u{FFFD}');
+                        pending.drain(..valid_up_to + len);
+                    }
+                    None => {
+                        pending.drain(..valid_up_to);
+                        return text;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Read a whole asciicast v2 file
+pub fn read_cast(path: &Path) -> Result<(CastHeader, Vec<CastEvent>), String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut lines = BufReader::new(file).lines();
+
+    let header_line = lines
+        .next()
+        .ok_or_else(|| format!("{} is empty", path.display()))?
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let header: CastHeader = serde_json::from_str(&header_line)
+        .map_err(|e| format!("Invalid asciicast header in {}: {}", path.display(), e))?;
+    if header.version != 2 {
+        return Err(format!("Unsupported asciicast version {}", header.version));
+    }
+
+    let mut events = Vec::new();
+    for (index, line) in lines.enumerate() {
+        let line = line.map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event: CastEvent = serde_json::from_str(&line)
+            .map_err(|e| format!("Invalid event on line {} of {}: {}", index + 2, path.display(), e))?;
+        events.push(event);
+    }
+
+    Ok((header, events))
+}
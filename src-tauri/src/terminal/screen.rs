@@ -0,0 +1,1208 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+use unicode_width::UnicodeWidthChar;
+use vte::{Params, Parser, Perform};
+
+use super::shell_integration::{self, ShellEvent, ShellIntegration};
+
+/// Default number of lines kept after they scroll off the primary screen
+pub const DEFAULT_SCROLLBACK_LINES: usize = 1000;
+
+const TAB_WIDTH: usize = 8;
+
+/// Foreground or background color of a cell
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "camelCase")]
+pub enum Color {
+    #[default]
+    Default,
+    /// One of the 256 palette colors, 0-15 are the theme's ANSI colors
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+/// SGR attributes of a cell
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CellAttrs {
+    pub fg: Color,
+    pub bg: Color,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub blink: bool,
+    pub inverse: bool,
+    pub hidden: bool,
+    pub strikethrough: bool,
+}
+
+impl CellAttrs {
+    /// Apply an SGR (`CSI ... m`) sequence
+    pub fn apply_sgr(&mut self, params: &Params) {
+        let groups: Vec<&[u16]> = params.iter().collect();
+        if groups.is_empty() {
+            *self = CellAttrs::default();
+            return;
+        }
+
+        let mut i = 0;
+        while i < groups.len() {
+            let group = groups[i];
+            match group[0] {
+                0 => *self = CellAttrs::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = group.get(1) != Some(&0),
+                5 | 6 => self.blink = true,
+                7 => self.inverse = true,
+                8 => self.hidden = true,
+                9 => self.strikethrough = true,
+                21 => self.underline = true,
+                22 => {
+                    self.bold = false;
+                    self.dim = false;
+                }
+                23 => self.italic = false,
+                24 => self.underline = false,
+                25 => self.blink = false,
+                27 => self.inverse = false,
+                28 => self.hidden = false,
+                29 => self.strikethrough = false,
+                n @ 30..=37 => self.fg = Color::Indexed((n - 30) as u8),
+                38 => {
+                    let (color, consumed) = parse_extended_color(&groups[i..]);
+                    if let Some(color) = color {
+                        self.fg = color;
+                    }
+                    i += consumed;
+                }
+                39 => self.fg = Color::Default,
+                n @ 40..=47 => self.bg = Color::Indexed((n - 40) as u8),
+                48 => {
+                    let (color, consumed) = parse_extended_color(&groups[i..]);
+                    if let Some(color) = color {
+                        self.bg = color;
+                    }
+                    i += consumed;
+                }
+                49 => self.bg = Color::Default,
+                n @ 90..=97 => self.fg = Color::Indexed((n - 90 + 8) as u8),
+                n @ 100..=107 => self.bg = Color::Indexed((n - 100 + 8) as u8),
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub c: char,
+    /// Columns taken by the character, 0 for the trailing half of a wide character
+    pub width: u8,
+    pub attrs: CellAttrs,
+}
+
+impl Cell {
+    /// Empty cell, erased cells keep the current background (BCE)
+    fn blank(attrs: CellAttrs) -> Self {
+        Self {
+            c: ' ',
+            width: 1,
+            attrs: CellAttrs {
+                bg: attrs.bg,
+                ..CellAttrs::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Row {
+    pub cells: Vec<Cell>,
+    /// The line continues on the next row because it was autowrapped
+    pub wrapped: bool,
+}
+
+impl Row {
+    fn new(cols: usize, attrs: CellAttrs) -> Self {
+        Self {
+            cells: vec![Cell::blank(attrs); cols],
+            wrapped: false,
+        }
+    }
+
+    /// Text of the row with trailing blanks removed
+    pub fn text(&self) -> String {
+        let text: String = self
+            .cells
+            .iter()
+            .filter(|cell| cell.width > 0)
+            .map(|cell| cell.c)
+            .collect();
+        text.trim_end().to_string()
+    }
+
+    fn resize(&mut self, cols: usize) {
+        self.cells.resize(cols, Cell::blank(CellAttrs::default()));
+        // Don't leave half of a wide character behind
+        if let Some(last) = self.cells.last_mut() {
+            if last.width > 1 {
+                *last = Cell::blank(last.attrs);
+            }
+        }
+    }
+}
+
+/// Zero-based cursor position on the visible screen
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CursorPosition {
+    pub row: usize,
+    pub col: usize,
+}
+
+/// DEC private and ANSI modes the backend cares about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TerminalModes {
+    /// DECCKM (?1)
+    pub application_cursor: bool,
+    /// DECAWM (?7)
+    pub autowrap: bool,
+    /// DECTCEM (?25)
+    pub cursor_visible: bool,
+    /// IRM (4)
+    pub insert: bool,
+    /// Bracketed paste (?2004)
+    pub bracketed_paste: bool,
+    /// Any of the mouse reporting modes (?1000, ?1002, ?1003)
+    pub mouse_tracking: bool,
+    /// Focus in/out reporting (?1004)
+    pub focus_reporting: bool,
+}
+
+impl Default for TerminalModes {
+    fn default() -> Self {
+        Self {
+            application_cursor: false,
+            autowrap: true,
+            cursor_visible: true,
+            insert: false,
+            bracketed_paste: false,
+            mouse_tracking: false,
+            focus_reporting: false,
+        }
+    }
+}
+
+/// Text view of the screen returned by `get_screen_snapshot`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScreenSnapshot {
+    pub cols: usize,
+    pub rows: usize,
+    /// Visible lines, top to bottom, without trailing blanks
+    pub lines: Vec<String>,
+    /// Lines that scrolled off the primary screen, oldest first. Empty unless requested
+    pub scrollback: Vec<String>,
+    pub cursor: CursorPosition,
+    pub alternate_screen: bool,
+    pub title: Option<String>,
+    pub modes: TerminalModes,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct SavedCursor {
+    row: usize,
+    col: usize,
+    attrs: CellAttrs,
+}
+
+/// Screen model fed with raw PTY output
+/// Mirrors what xterm.js shows closely enough to answer questions about the screen
+/// (contents, cursor, alternate screen, modes) without asking the webview.
+pub struct TerminalScreen {
+    parser: Parser,
+    state: ScreenState,
+}
+
+impl TerminalScreen {
+    pub fn new(cols: usize, rows: usize, scrollback_lines: usize) -> Self {
+        Self {
+            parser: Parser::new(),
+            state: ScreenState::new(cols.max(1), rows.max(1), scrollback_lines),
+        }
+    }
+
+    /// Feed raw output, escape sequences may be split across calls
+    pub fn process(&mut self, bytes: &[u8]) {
+        self.parser.advance(&mut self.state, bytes);
+    }
+
+    /// Feed output from before this session, e.g. the history of a restored session
+    /// Commands and cwd it reports belong to the old shell, so no events come out of it.
+    pub fn process_history(&mut self, bytes: &[u8]) {
+        self.process(bytes);
+        self.state.shell = ShellIntegration::default();
+    }
+
+    pub fn resize(&mut self, cols: usize, rows: usize) {
+        self.state.resize(cols.max(1), rows.max(1));
+    }
+
+    pub fn cols(&self) -> usize {
+        self.state.cols
+    }
+
+    pub fn rows(&self) -> usize {
+        self.state.rows
+    }
+
+    pub fn cursor(&self) -> CursorPosition {
+        CursorPosition {
+            row: self.state.cursor_row,
+            col: self.state.cursor_col,
+        }
+    }
+
+    pub fn is_alternate_screen(&self) -> bool {
+        self.state.alternate_active
+    }
+
+    pub fn modes(&self) -> TerminalModes {
+        self.state.modes
+    }
+
+    /// Title set through OSC 0 or OSC 2
+    pub fn title(&self) -> Option<&str> {
+        self.state.title.as_deref()
+    }
+
+    /// Rows of the active screen
+    pub fn visible_rows(&self) -> &[Row] {
+        self.state.grid()
+    }
+
+    /// Rows that scrolled off the primary screen, oldest first
+    pub fn scrollback_rows(&self) -> impl Iterator<Item = &Row> {
+        self.state.scrollback.iter()
+    }
+
+    /// Command boundaries and cwd reported by the shell
+    pub fn shell_integration(&self) -> &ShellIntegration {
+        &self.state.shell
+    }
+
+    /// Shell integration events produced since the last call
+    pub fn take_shell_events(&mut self) -> Vec<ShellEvent> {
+        self.state.shell.take_events()
+    }
+
+    /// Visible lines as text
+    pub fn lines(&self) -> Vec<String> {
+        self.visible_rows().iter().map(Row::text).collect()
+    }
+
+    pub fn snapshot(&self, include_scrollback: bool) -> ScreenSnapshot {
+        let scrollback = if include_scrollback {
+            self.scrollback_rows().map(Row::text).collect()
+        } else {
+            Vec::new()
+        };
+
+        ScreenSnapshot {
+            cols: self.state.cols,
+            rows: self.state.rows,
+            lines: self.lines(),
+            scrollback,
+            cursor: self.cursor(),
+            alternate_screen: self.state.alternate_active,
+            title: self.state.title.clone(),
+            modes: self.state.modes,
+        }
+    }
+}
+
+struct ScreenState {
+    cols: usize,
+    rows: usize,
+    primary: Vec<Row>,
+    alternate: Vec<Row>,
+    alternate_active: bool,
+    scrollback: VecDeque<Row>,
+    scrollback_limit: usize,
+    cursor_row: usize,
+    cursor_col: usize,
+    /// Cursor sits past the last column, the next printed character wraps first
+    pending_wrap: bool,
+    attrs: CellAttrs,
+    saved_primary: SavedCursor,
+    saved_alternate: SavedCursor,
+    /// Scroll region, inclusive
+    scroll_top: usize,
+    scroll_bottom: usize,
+    modes: TerminalModes,
+    title: Option<String>,
+    last_char: Option<char>,
+    /// Lines scrolled off the top of the primary screen so far, gives rows an absolute index
+    lines_scrolled: u64,
+    shell: ShellIntegration,
+}
+
+impl ScreenState {
+    fn new(cols: usize, rows: usize, scrollback_limit: usize) -> Self {
+        Self {
+            cols,
+            rows,
+            primary: Self::blank_grid(cols, rows),
+            alternate: Self::blank_grid(cols, rows),
+            alternate_active: false,
+            scrollback: VecDeque::new(),
+            scrollback_limit,
+            cursor_row: 0,
+            cursor_col: 0,
+            pending_wrap: false,
+            attrs: CellAttrs::default(),
+            saved_primary: SavedCursor::default(),
+            saved_alternate: SavedCursor::default(),
+            scroll_top: 0,
+            scroll_bottom: rows - 1,
+            modes: TerminalModes::default(),
+            title: None,
+            last_char: None,
+            lines_scrolled: 0,
+            shell: ShellIntegration::default(),
+        }
+    }
+
+    fn blank_grid(cols: usize, rows: usize) -> Vec<Row> {
+        (0..rows).map(|_| Row::new(cols, CellAttrs::default())).collect()
+    }
+
+    fn grid(&self) -> &Vec<Row> {
+        if self.alternate_active {
+            &self.alternate
+        } else {
+            &self.primary
+        }
+    }
+
+    fn grid_mut(&mut self) -> &mut Vec<Row> {
+        if self.alternate_active {
+            &mut self.alternate
+        } else {
+            &mut self.primary
+        }
+    }
+
+    fn blank_row(&self) -> Row {
+        Row::new(self.cols, self.attrs)
+    }
+
+    fn push_scrollback(&mut self, row: Row) {
+        if self.scrollback_limit == 0 {
+            return;
+        }
+        if self.scrollback.len() >= self.scrollback_limit {
+            self.scrollback.pop_front();
+        }
+        self.scrollback.push_back(row);
+    }
+
+    fn resize(&mut self, cols: usize, rows: usize) {
+        if cols != self.cols {
+            for row in self.primary.iter_mut().chain(self.alternate.iter_mut()) {
+                row.resize(cols);
+            }
+            self.cols = cols;
+        }
+
+        if rows < self.rows {
+            let excess = self.rows - rows;
+            // Drop blank rows below the cursor first, then push rows off the top
+            let below_cursor = self.rows - 1 - self.cursor_row;
+            let from_bottom = excess.min(below_cursor);
+            let from_top = excess - from_bottom;
+
+            for grid in [&mut self.primary, &mut self.alternate] {
+                grid.truncate(grid.len() - from_bottom);
+            }
+            let removed: Vec<Row> = self.primary.drain(..from_top).collect();
+            self.alternate.drain(..from_top);
+            for row in removed {
+                self.push_scrollback(row);
+            }
+            self.lines_scrolled += from_top as u64;
+            self.cursor_row -= from_top;
+        } else if rows > self.rows {
+            for grid in [&mut self.primary, &mut self.alternate] {
+                grid.resize(rows, Row::new(cols, CellAttrs::default()));
+            }
+        }
+
+        self.rows = rows;
+        self.scroll_top = 0;
+        self.scroll_bottom = rows - 1;
+        self.cursor_row = self.cursor_row.min(rows - 1);
+        self.cursor_col = self.cursor_col.min(cols - 1);
+        self.pending_wrap = false;
+    }
+
+    fn scroll_up(&mut self, count: usize) {
+        let (top, bottom) = (self.scroll_top, self.scroll_bottom);
+        let count = count.min(bottom - top + 1);
+        let to_scrollback = !self.alternate_active && top == 0;
+
+        for _ in 0..count {
+            let blank = self.blank_row();
+            let grid = self.grid_mut();
+            let row = grid.remove(top);
+            grid.insert(bottom, blank);
+            if to_scrollback {
+                self.push_scrollback(row);
+                self.lines_scrolled += 1;
+            }
+        }
+    }
+
+    fn scroll_down(&mut self, count: usize) {
+        let (top, bottom) = (self.scroll_top, self.scroll_bottom);
+        let count = count.min(bottom - top + 1);
+
+        for _ in 0..count {
+            let blank = self.blank_row();
+            let grid = self.grid_mut();
+            grid.remove(bottom);
+            grid.insert(top, blank);
+        }
+    }
+
+    fn linefeed(&mut self) {
+        self.pending_wrap = false;
+        if self.cursor_row == self.scroll_bottom {
+            self.scroll_up(1);
+        } else if self.cursor_row < self.rows - 1 {
+            self.cursor_row += 1;
+        }
+    }
+
+    fn reverse_index(&mut self) {
+        self.pending_wrap = false;
+        if self.cursor_row == self.scroll_top {
+            self.scroll_down(1);
+        } else if self.cursor_row > 0 {
+            self.cursor_row -= 1;
+        }
+    }
+
+    fn move_to(&mut self, row: usize, col: usize) {
+        self.cursor_row = row.min(self.rows - 1);
+        self.cursor_col = col.min(self.cols - 1);
+        self.pending_wrap = false;
+    }
+
+    /// Blank both halves of a wide character that is about to be partly overwritten
+    fn clear_wide_at(&mut self, row: usize, col: usize) {
+        let attrs = self.attrs;
+        let cols = self.cols;
+        let cells = &mut self.grid_mut()[row].cells;
+        match cells[col].width {
+            0 if col > 0 => cells[col - 1] = Cell::blank(attrs),
+            2 if col + 1 < cols => cells[col + 1] = Cell::blank(attrs),
+            _ => {}
+        }
+    }
+
+    fn print_char(&mut self, c: char) {
+        let width = match c.width() {
+            Some(width) if width > 0 => width.min(2),
+            // Combining marks and other zero-width characters aren't tracked
+            _ => return,
+        };
+
+        if self.pending_wrap && self.modes.autowrap {
+            let row = self.cursor_row;
+            self.grid_mut()[row].wrapped = true;
+            self.cursor_col = 0;
+            self.linefeed();
+        }
+        if width == 2 && self.cursor_col + 1 >= self.cols {
+            if !self.modes.autowrap || self.cols < 2 {
+                return;
+            }
+            let row = self.cursor_row;
+            self.grid_mut()[row].wrapped = true;
+            self.cursor_col = 0;
+            self.linefeed();
+        }
+
+        let (row, col, attrs, cols) = (self.cursor_row, self.cursor_col, self.attrs, self.cols);
+        if self.modes.insert {
+            let cells = &mut self.grid_mut()[row].cells;
+            for _ in 0..width {
+                cells.insert(col, Cell::blank(attrs));
+            }
+            cells.truncate(cols);
+        }
+
+        self.clear_wide_at(row, col);
+        if width == 2 {
+            self.clear_wide_at(row, col + 1);
+        }
+        let cells = &mut self.grid_mut()[row].cells;
+        cells[col] = Cell {
+            c,
+            width: width as u8,
+            attrs,
+        };
+        if width == 2 {
+            cells[col + 1] = Cell {
+                c: ' ',
+                width: 0,
+                attrs,
+            };
+        }
+
+        self.last_char = Some(c);
+        if col + width >= self.cols {
+            self.cursor_col = self.cols - 1;
+            self.pending_wrap = self.modes.autowrap;
+        } else {
+            self.cursor_col = col + width;
+        }
+    }
+
+    fn erase_cells(&mut self, row: usize, from: usize, to: usize) {
+        let blank = Cell::blank(self.attrs);
+        let cols = self.cols;
+        if from >= to.min(cols) {
+            return;
+        }
+        self.clear_wide_at(row, from);
+        self.clear_wide_at(row, to.min(cols) - 1);
+        let cells = &mut self.grid_mut()[row].cells;
+        for cell in &mut cells[from.min(cols)..to.min(cols)] {
+            *cell = blank;
+        }
+    }
+
+    fn erase_in_display(&mut self, mode: u16) {
+        let (row, col) = (self.cursor_row, self.cursor_col);
+        match mode {
+            0 => {
+                self.erase_cells(row, col, self.cols);
+                for r in row + 1..self.rows {
+                    self.erase_cells(r, 0, self.cols);
+                }
+            }
+            1 => {
+                for r in 0..row {
+                    self.erase_cells(r, 0, self.cols);
+                }
+                self.erase_cells(row, 0, col + 1);
+            }
+            2 => {
+                for r in 0..self.rows {
+                    self.erase_cells(r, 0, self.cols);
+                }
+            }
+            3 => self.scrollback.clear(),
+            _ => {}
+        }
+        if mode != 3 {
+            for r in 0..self.rows {
+                self.grid_mut()[r].wrapped = false;
+            }
+        }
+    }
+
+    fn erase_in_line(&mut self, mode: u16) {
+        let (row, col) = (self.cursor_row, self.cursor_col);
+        match mode {
+            0 => self.erase_cells(row, col, self.cols),
+            1 => self.erase_cells(row, 0, col + 1),
+            2 => self.erase_cells(row, 0, self.cols),
+            _ => {}
+        }
+    }
+
+    fn insert_lines(&mut self, count: usize) {
+        let row = self.cursor_row;
+        if row < self.scroll_top || row > self.scroll_bottom {
+            return;
+        }
+        let bottom = self.scroll_bottom;
+        for _ in 0..count.min(bottom - row + 1) {
+            let blank = self.blank_row();
+            let grid = self.grid_mut();
+            grid.remove(bottom);
+            grid.insert(row, blank);
+        }
+        self.cursor_col = 0;
+        self.pending_wrap = false;
+    }
+
+    fn delete_lines(&mut self, count: usize) {
+        let row = self.cursor_row;
+        if row < self.scroll_top || row > self.scroll_bottom {
+            return;
+        }
+        let bottom = self.scroll_bottom;
+        for _ in 0..count.min(bottom - row + 1) {
+            let blank = self.blank_row();
+            let grid = self.grid_mut();
+            grid.remove(row);
+            grid.insert(bottom, blank);
+        }
+        self.cursor_col = 0;
+        self.pending_wrap = false;
+    }
+
+    fn insert_chars(&mut self, count: usize) {
+        let (row, col, cols, blank) = (self.cursor_row, self.cursor_col, self.cols, Cell::blank(self.attrs));
+        let cells = &mut self.grid_mut()[row].cells;
+        for _ in 0..count.min(cols - col) {
+            cells.insert(col, blank);
+        }
+        cells.truncate(cols);
+    }
+
+    fn delete_chars(&mut self, count: usize) {
+        let (row, col, cols, blank) = (self.cursor_row, self.cursor_col, self.cols, Cell::blank(self.attrs));
+        let cells = &mut self.grid_mut()[row].cells;
+        let count = count.min(cols - col);
+        cells.drain(col..col + count);
+        cells.resize(cols, blank);
+    }
+
+    fn save_cursor(&mut self) {
+        let saved = SavedCursor {
+            row: self.cursor_row,
+            col: self.cursor_col,
+            attrs: self.attrs,
+        };
+        if self.alternate_active {
+            self.saved_alternate = saved;
+        } else {
+            self.saved_primary = saved;
+        }
+    }
+
+    fn restore_cursor(&mut self) {
+        let saved = if self.alternate_active {
+            self.saved_alternate
+        } else {
+            self.saved_primary
+        };
+        self.attrs = saved.attrs;
+        self.move_to(saved.row, saved.col);
+    }
+
+    fn set_alternate_screen(&mut self, active: bool, save_cursor: bool) {
+        if active == self.alternate_active {
+            return;
+        }
+        if active {
+            if save_cursor {
+                self.save_cursor();
+            }
+            self.alternate_active = true;
+            self.alternate = Self::blank_grid(self.cols, self.rows);
+        } else {
+            self.alternate_active = false;
+            if save_cursor {
+                self.restore_cursor();
+            }
+        }
+        self.pending_wrap = false;
+    }
+
+    fn reset(&mut self) {
+        // RIS resets the terminal, not what the shell has told us
+        let shell = std::mem::take(&mut self.shell);
+        *self = Self::new(self.cols, self.rows, self.scrollback_limit);
+        self.shell = shell;
+    }
+
+    /// Text typed since the absolute position `(line, col)`, used to recover the command
+    /// line when the shell only marks where it starts
+    fn text_since(&self, line: u64, col: usize) -> Option<String> {
+        let (start_row, start_col) = if line < self.lines_scrolled {
+            (0, 0)
+        } else {
+            ((line - self.lines_scrolled) as usize, col)
+        };
+        // The cursor has usually moved to the next line when the command is submitted
+        let end_row = if self.cursor_col == 0 && self.cursor_row > start_row {
+            self.cursor_row - 1
+        } else {
+            self.cursor_row
+        };
+        if start_row > end_row || end_row >= self.rows {
+            return None;
+        }
+
+        let mut text = String::new();
+        for (index, row) in self.grid()[start_row..=end_row].iter().enumerate() {
+            let from = if index == 0 { start_col.min(self.cols) } else { 0 };
+            let line: String = row.cells[from..]
+                .iter()
+                .filter(|cell| cell.width > 0)
+                .map(|cell| cell.c)
+                .collect();
+            if row.wrapped {
+                text.push_str(&line);
+            } else {
+                text.push_str(line.trim_end());
+                text.push('\n');
+            }
+        }
+
+        let text = text.trim();
+        if text.is_empty() {
+            None
+        } else {
+            Some(text.to_string())
+        }
+    }
+
+    /// OSC 133 semantic prompt marks
+    fn handle_prompt_mark(&mut self, params: &[&[u8]]) {
+        let Some(mark) = params.get(1).and_then(|mark| mark.first()) else {
+            return;
+        };
+        match mark {
+            b'A' => self.shell.prompt_started(),
+            b'B' => {
+                let line = self.lines_scrolled + self.cursor_row as u64;
+                self.shell.input_started(line, self.cursor_col);
+            }
+            b'C' => {
+                let reported = params[2..].iter().find_map(|param| {
+                    let param = String::from_utf8_lossy(param);
+                    if let Some(url) = param.strip_prefix("cmdline_url=") {
+                        Some(shell_integration::percent_decode(url))
+                    } else {
+                        param.strip_prefix("cmdline=").map(|cmd| cmd.to_string())
+                    }
+                });
+                let command = reported
+                    .filter(|cmd| !cmd.trim().is_empty())
+                    .or_else(|| {
+                        let (line, col) = self.shell.input_start()?;
+                        self.text_since(line, col)
+                    });
+                self.shell.command_executed(command);
+            }
+            b'D' => {
+                let exit_code = params
+                    .get(2)
+                    .and_then(|code| std::str::from_utf8(code).ok())
+                    .and_then(|code| code.trim().parse().ok());
+                self.shell.finish_command(exit_code);
+            }
+            _ => {}
+        }
+    }
+
+    fn set_private_mode(&mut self, mode: u16, enabled: bool) {
+        match mode {
+            1 => self.modes.application_cursor = enabled,
+            7 => self.modes.autowrap = enabled,
+            25 => self.modes.cursor_visible = enabled,
+            47 | 1047 => self.set_alternate_screen(enabled, false),
+            1049 => self.set_alternate_screen(enabled, true),
+            1000 | 1002 | 1003 => self.modes.mouse_tracking = enabled,
+            1004 => self.modes.focus_reporting = enabled,
+            2004 => self.modes.bracketed_paste = enabled,
+            _ => {}
+        }
+    }
+
+    fn set_mode(&mut self, mode: u16, enabled: bool) {
+        if mode == 4 {
+            self.modes.insert = enabled;
+        }
+    }
+
+    fn set_scroll_region(&mut self, top: usize, bottom: usize) {
+        let bottom = if bottom == 0 { self.rows } else { bottom.min(self.rows) };
+        let top = top.max(1);
+        if top < bottom {
+            self.scroll_top = top - 1;
+            self.scroll_bottom = bottom - 1;
+            self.move_to(0, 0);
+        }
+    }
+
+}
+
+/// Parse `38;5;n`, `38;2;r;g;b` or their colon forms `38:5:n`, `38:2::r:g:b`
+/// Returns the color and how many extra parameter groups were consumed.
+fn parse_extended_color(groups: &[&[u16]]) -> (Option<Color>, usize) {
+    let first = groups[0];
+    if first.len() > 1 {
+        // Colon-separated subparameters, everything is in this group
+        let color = match first[1] {
+            5 => first.get(2).map(|&n| Color::Indexed(n as u8)),
+            2 => {
+                // An optional color space ID may precede the components
+                let components = if first.len() >= 6 { &first[3..6] } else { &first[2..] };
+                match components {
+                    [r, g, b, ..] => Some(Color::Rgb(*r as u8, *g as u8, *b as u8)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        return (color, 0);
+    }
+
+    let value = |index: usize| groups.get(index).map(|group| group[0]);
+    match value(1) {
+        Some(5) => (value(2).map(|n| Color::Indexed(n as u8)), 2),
+        Some(2) => match (value(2), value(3), value(4)) {
+            (Some(r), Some(g), Some(b)) => (Some(Color::Rgb(r as u8, g as u8, b as u8)), 4),
+            _ => (None, groups.len() - 1),
+        },
+        _ => (None, 0),
+    }
+}
+
+/// Parameter at `index`, with 0 or a missing value replaced by `default`
+fn param(params: &Params, index: usize, default: u16) -> usize {
+    let value = params
+        .iter()
+        .nth(index)
+        .map(|group| group[0])
+        .unwrap_or(0);
+    if value == 0 {
+        default as usize
+    } else {
+        value as usize
+    }
+}
+
+impl Perform for ScreenState {
+    fn print(&mut self, c: char) {
+        self.print_char(c);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            // BS
+            0x08 => {
+                self.cursor_col = self.cursor_col.saturating_sub(1);
+                self.pending_wrap = false;
+            }
+            // HT
+            0x09 => {
+                let next = (self.cursor_col / TAB_WIDTH + 1) * TAB_WIDTH;
+                self.cursor_col = next.min(self.cols - 1);
+            }
+            // LF, VT, FF
+            0x0A..=0x0C => self.linefeed(),
+            // CR
+            0x0D => {
+                self.cursor_col = 0;
+                self.pending_wrap = false;
+            }
+            _ => {}
+        }
+    }
+
+    fn osc_dispatch(&mut self, params: &[&[u8]], _bell_terminated: bool) {
+        match params {
+            [b"0", title, ..] | [b"2", title, ..] => {
+                let title = String::from_utf8_lossy(title).to_string();
+                self.title = if title.is_empty() { None } else { Some(title) };
+            }
+            [b"7", url @ ..] if !url.is_empty() => {
+                // A `;` in the path splits the URL into several params
+                let url = String::from_utf8_lossy(&url.join(&b';')).to_string();
+                if let Some(cwd) = shell_integration::parse_cwd_url(&url) {
+                    self.shell.set_cwd(cwd);
+                }
+            }
+            [b"133", ..] => self.handle_prompt_mark(params),
+            _ => {}
+        }
+    }
+
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], ignore: bool, action: char) {
+        if ignore {
+            return;
+        }
+        let private = intermediates.first() == Some(&b'?');
+        if !private && !intermediates.is_empty() {
+            // DECSCUSR (` q`) and friends don't affect the screen contents
+            return;
+        }
+
+        let (row, col) = (self.cursor_row, self.cursor_col);
+        match (private, action) {
+            (false, 'A') => self.move_to(row.saturating_sub(param(params, 0, 1)), col),
+            (false, 'B') | (false, 'e') => self.move_to(row + param(params, 0, 1), col),
+            (false, 'C') | (false, 'a') => self.move_to(row, col + param(params, 0, 1)),
+            (false, 'D') => self.move_to(row, col.saturating_sub(param(params, 0, 1))),
+            (false, 'E') => self.move_to(row + param(params, 0, 1), 0),
+            (false, 'F') => self.move_to(row.saturating_sub(param(params, 0, 1)), 0),
+            (false, 'G') | (false, '`') => self.move_to(row, param(params, 0, 1) - 1),
+            (false, 'H') | (false, 'f') => {
+                self.move_to(param(params, 0, 1) - 1, param(params, 1, 1) - 1)
+            }
+            (false, 'd') => self.move_to(param(params, 0, 1) - 1, col),
+            (false, 'J') | (true, 'J') => self.erase_in_display(param(params, 0, 0) as u16),
+            (false, 'K') | (true, 'K') => self.erase_in_line(param(params, 0, 0) as u16),
+            (false, 'L') => self.insert_lines(param(params, 0, 1)),
+            (false, 'M') => self.delete_lines(param(params, 0, 1)),
+            (false, '@') => self.insert_chars(param(params, 0, 1)),
+            (false, 'P') => self.delete_chars(param(params, 0, 1)),
+            (false, 'X') => {
+                let count = param(params, 0, 1);
+                self.erase_cells(row, col, col + count);
+            }
+            (false, 'S') => self.scroll_up(param(params, 0, 1)),
+            (false, 'T') => self.scroll_down(param(params, 0, 1)),
+            (false, 'b') => {
+                if let Some(c) = self.last_char {
+                    for _ in 0..param(params, 0, 1).min(self.cols * self.rows) {
+                        self.print_char(c);
+                    }
+                }
+            }
+            (false, 'm') => self.attrs.apply_sgr(params),
+            (false, 'r') => self.set_scroll_region(param(params, 0, 1), param(params, 1, 0)),
+            (false, 's') => self.save_cursor(),
+            (false, 'u') => self.restore_cursor(),
+            (false, 'h') | (false, 'l') | (true, 'h') | (true, 'l') => {
+                let enabled = action == 'h';
+                for group in params.iter() {
+                    if private {
+                        self.set_private_mode(group[0], enabled);
+                    } else {
+                        self.set_mode(group[0], enabled);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn esc_dispatch(&mut self, intermediates: &[u8], _ignore: bool, byte: u8) {
+        if !intermediates.is_empty() {
+            // Charset designations and DECALN
+            return;
+        }
+        match byte {
+            b'7' => self.save_cursor(),
+            b'8' => self.restore_cursor(),
+            b'D' => self.linefeed(),
+            b'E' => {
+                self.cursor_col = 0;
+                self.linefeed();
+            }
+            b'M' => self.reverse_index(),
+            b'c' => self.reset(),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen(cols: usize, rows: usize, output: &str) -> TerminalScreen {
+        let mut screen = TerminalScreen::new(cols, rows, DEFAULT_SCROLLBACK_LINES);
+        screen.process(output.as_bytes());
+        screen
+    }
+
+    fn cursor(screen: &TerminalScreen) -> (usize, usize) {
+        let cursor = screen.cursor();
+        (cursor.row, cursor.col)
+    }
+
+    #[test]
+    fn cursor_movement() {
+        let mut screen = screen(10, 5, "\x1b[3;4H");
+        assert_eq!(cursor(&screen), (2, 3));
+        screen.process(b"\x1b[A\x1b[2C");
+        assert_eq!(cursor(&screen), (1, 5));
+        screen.process(b"\x1b[10B\x1b[20D");
+        assert_eq!(cursor(&screen), (4, 0), "movement is clamped to the screen");
+        screen.process(b"\x1b[7G\x1b[2d");
+        assert_eq!(cursor(&screen), (1, 6));
+        screen.process(b"\x1b7\x1b[H\x1b8");
+        assert_eq!(cursor(&screen), (1, 6), "DECRC restores the DECSC position");
+        screen.process(b"x\r\n");
+        assert_eq!(cursor(&screen), (2, 0));
+        assert_eq!(screen.lines()[1], "      x");
+    }
+
+    #[test]
+    fn autowrap_continues_on_the_next_row() {
+        let screen = screen(5, 3, "abcdefgh");
+        assert_eq!(screen.lines(), vec!["abcde", "fgh", ""]);
+        assert!(screen.visible_rows()[0].wrapped);
+        assert!(!screen.visible_rows()[1].wrapped);
+        assert_eq!(cursor(&screen), (1, 3));
+    }
+
+    #[test]
+    fn pending_wrap_is_cleared_by_carriage_return() {
+        let screen = screen(5, 3, "abcde\rX");
+        assert_eq!(screen.lines(), vec!["Xbcde", "", ""]);
+        assert_eq!(cursor(&screen), (0, 1));
+    }
+
+    #[test]
+    fn without_autowrap_the_last_column_is_overwritten() {
+        let screen = screen(5, 3, "\x1b[?7labcdefg");
+        assert_eq!(screen.lines(), vec!["abcdg", "", ""]);
+        assert!(!screen.modes().autowrap);
+    }
+
+    #[test]
+    fn wide_characters_take_two_columns() {
+        let screen = screen(5, 2, "日本語");
+        assert_eq!(screen.lines(), vec!["日本", "語"]);
+        assert_eq!(screen.visible_rows()[0].cells[1].width, 0);
+    }
+
+    #[test]
+    fn lines_scrolled_off_go_to_scrollback() {
+        let screen = screen(10, 2, "one\r\ntwo\r\nthree");
+        assert_eq!(screen.lines(), vec!["two", "three"]);
+        let scrollback: Vec<String> = screen.scrollback_rows().map(Row::text).collect();
+        assert_eq!(scrollback, vec!["one"]);
+    }
+
+    #[test]
+    fn scroll_region_keeps_the_rows_outside_it() {
+        let mut screen = screen(10, 5, "top\r\n1\r\n2\r\n3\r\nbottom");
+        // Region rows 2-4, then scroll it by writing a newline on its last row
+        screen.process(b"\x1b[2;4r");
+        assert_eq!(cursor(&screen), (0, 0), "DECSTBM homes the cursor");
+        screen.process(b"\x1b[4;1H\nnew");
+        assert_eq!(screen.lines(), vec!["top", "2", "3", "new", "bottom"]);
+        assert_eq!(screen.scrollback_rows().count(), 0, "a region scroll doesn't reach scrollback");
+
+        screen.process(b"\x1b[2;1H\x1bM");
+        assert_eq!(screen.lines(), vec!["top", "", "2", "3", "bottom"]);
+    }
+
+    #[test]
+    fn insert_and_delete_lines_stay_in_the_scroll_region() {
+        let mut screen = screen(10, 4, "a\r\nb\r\nc\r\nd");
+        screen.process(b"\x1b[1;3r\x1b[2;1H\x1b[L");
+        assert_eq!(screen.lines(), vec!["a", "", "b", "d"]);
+        screen.process(b"\x1b[M\x1b[M");
+        assert_eq!(screen.lines(), vec!["a", "", "", "d"]);
+    }
+
+    #[test]
+    fn alternate_screen_restores_the_primary_screen() {
+        let mut screen = screen(20, 3, "prompt$ vim");
+        screen.process(b"\x1b[?1049h");
+        assert!(screen.is_alternate_screen());
+        assert_eq!(screen.lines(), vec!["", "", ""]);
+        screen.process(b"\x1b[2;3Hediting");
+        assert_eq!(screen.lines()[1], "  editing");
+
+        screen.process(b"\x1b[?1049l");
+        assert!(!screen.is_alternate_screen());
+        assert_eq!(screen.lines(), vec!["prompt$ vim", "", ""]);
+        assert_eq!(cursor(&screen), (0, 11), "1049 restores the cursor");
+    }
+
+    #[test]
+    fn alternate_screen_output_doesnt_reach_scrollback() {
+        let screen = screen(10, 2, "\x1b[?1049h1\r\n2\r\n3\r\n4");
+        assert_eq!(screen.scrollback_rows().count(), 0);
+    }
+
+    #[test]
+    fn erase_in_line_and_display() {
+        let mut screen = screen(10, 3, "abcdef\r\nghijkl\r\nmnopqr");
+        screen.process(b"\x1b[1;4H\x1b[K");
+        assert_eq!(screen.lines()[0], "abc");
+        screen.process(b"\x1b[2;3H\x1b[1K");
+        assert_eq!(screen.lines()[1], "   jkl");
+        screen.process(b"\x1b[2;1H\x1b[J");
+        assert_eq!(screen.lines(), vec!["abc", "", ""]);
+        screen.process(b"\x1b[2J");
+        assert_eq!(screen.lines(), vec!["", "", ""]);
+    }
+
+    #[test]
+    fn sgr_sets_and_resets_attributes() {
+        let screen = screen(20, 1, "\x1b[1;3;31mA\x1b[22;39;44mB\x1b[38;5;200;48;2;1;2;3mC\x1b[0mD");
+        let cells = &screen.visible_rows()[0].cells;
+
+        assert!(cells[0].attrs.bold && cells[0].attrs.italic);
+        assert_eq!(cells[0].attrs.fg, Color::Indexed(1));
+
+        assert!(!cells[1].attrs.bold && cells[1].attrs.italic);
+        assert_eq!(cells[1].attrs.fg, Color::Default);
+        assert_eq!(cells[1].attrs.bg, Color::Indexed(4));
+
+        assert_eq!(cells[2].attrs.fg, Color::Indexed(200));
+        assert_eq!(cells[2].attrs.bg, Color::Rgb(1, 2, 3));
+
+        assert_eq!(cells[3].attrs, CellAttrs::default());
+    }
+
+    #[test]
+    fn sgr_colon_subparameters() {
+        let screen = screen(10, 1, "\x1b[38:2::10:20:30m\x1b[4:0mA");
+        let attrs = screen.visible_rows()[0].cells[0].attrs;
+        assert_eq!(attrs.fg, Color::Rgb(10, 20, 30));
+        assert!(!attrs.underline);
+    }
+
+    #[test]
+    fn erased_cells_keep_the_background() {
+        let screen = screen(4, 1, "\x1b[41m\x1b[K");
+        let cell = screen.visible_rows()[0].cells[3];
+        assert_eq!(cell.attrs.bg, Color::Indexed(1));
+        assert_eq!(cell.c, ' ');
+    }
+
+    #[test]
+    fn modes_and_title() {
+        let screen = screen(10, 2, "\x1b[?1h\x1b[?25l\x1b[?2004h\x1b]2;build\x07");
+        let modes = screen.modes();
+        assert!(modes.application_cursor);
+        assert!(!modes.cursor_visible);
+        assert!(modes.bracketed_paste);
+        assert_eq!(screen.title(), Some("build"));
+    }
+
+    #[test]
+    fn sequences_split_across_reads() {
+        let mut screen = screen(10, 2, "\x1b[");
+        screen.process(b"2;");
+        screen.process(b"5Hx");
+        assert_eq!(screen.lines()[1], "    x");
+    }
+
+    #[test]
+    fn history_produces_no_shell_events() {
+        let mut screen = TerminalScreen::new(20, 3, DEFAULT_SCROLLBACK_LINES);
+        let history = "\x1b]7;file://host/tmp\x07\x1b]133;A\x07$ \x1b]133;B\x07make\r\n\x1b]133;C\x07";
+        screen.process_history(history.as_bytes());
+        assert!(screen.take_shell_events().is_empty());
+        assert!(!screen.shell_integration().is_command_running());
+        assert_eq!(screen.shell_integration().cwd(), None);
+        assert_eq!(screen.lines()[0], "$ make");
+
+        // The new shell's first prompt doesn't finish the old command
+        screen.process(b"\x1b]133;D;0\x07\x1b]133;A\x07$ ");
+        assert!(screen.take_shell_events().is_empty());
+    }
+}
@@ -0,0 +1,94 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+/// Default number of bytes of recent output kept per session
+pub const DEFAULT_SCROLLBACK_BYTES: usize = 1024 * 1024;
+
+/// How far past the cut we look for a line break when trimming a snapshot
+const LINE_SEARCH_LIMIT: usize = 4096;
+
+/// Recent PTY output returned by `get_session_snapshot`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrollbackSnapshot {
+    /// Raw output bytes, ready to be written into xterm.js
+    pub data: Vec<u8>,
+    /// Total bytes the session has produced, including those no longer buffered
+    pub total_bytes: u64,
+    /// True if older output was dropped from the buffer
+    pub truncated: bool,
+}
+
+/// Bounded ring buffer of raw PTY output
+/// Once full, the oldest bytes are dropped to make room for new output.
+#[derive(Debug)]
+pub struct ScrollbackBuffer {
+    data: VecDeque<u8>,
+    capacity: usize,
+    total_bytes: u64,
+}
+
+impl ScrollbackBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            data: VecDeque::with_capacity(capacity.min(DEFAULT_SCROLLBACK_BYTES)),
+            capacity,
+            total_bytes: 0,
+        }
+    }
+
+    /// Append output, dropping the oldest bytes beyond capacity
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.total_bytes += bytes.len() as u64;
+        if self.capacity == 0 {
+            return;
+        }
+
+        let bytes = if bytes.len() > self.capacity {
+            &bytes[bytes.len() - self.capacity..]
+        } else {
+            bytes
+        };
+        let overflow = (self.data.len() + bytes.len()).saturating_sub(self.capacity);
+        self.data.drain(..overflow);
+        self.data.extend(bytes);
+    }
+
+    /// Copy the buffered output
+    pub fn snapshot(&self) -> ScrollbackSnapshot {
+        self.snapshot_tail(self.capacity)
+    }
+
+    /// Copy at most the last `max_bytes` of buffered output
+    /// If older output is left out, the copy starts at the next line so replay doesn't
+    /// begin halfway through a UTF-8 character or escape sequence.
+    pub fn snapshot_tail(&self, max_bytes: usize) -> ScrollbackSnapshot {
+        let start = self.data.len().saturating_sub(max_bytes);
+        let truncated = self.total_bytes > (self.data.len() - start) as u64;
+        let skip = if truncated {
+            self.data
+                .iter()
+                .skip(start)
+                .take(LINE_SEARCH_LIMIT)
+                .position(|&b| b == b'\n')
+                .map(|pos| pos + 1)
+                .unwrap_or_else(|| {
+                    // No line break nearby, at least skip UTF-8 continuation bytes
+                    self.data
+                        .iter()
+                        .skip(start)
+                        .take_while(|&&b| b & 0xC0 == 0x80)
+                        .count()
+                })
+        } else {
+            0
+        };
+
+        ScrollbackSnapshot {
+            data: self.data.iter().skip(start + skip).copied().collect(),
+            total_bytes: self.total_bytes,
+            truncated,
+        }
+    }
+}
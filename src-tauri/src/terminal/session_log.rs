@@ -0,0 +1,438 @@
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use unicode_width::UnicodeWidthChar;
+use vte::{Params, Parser, Perform};
+
+use super::screen::{CellAttrs, Color};
+
+const DEFAULT_MAX_BYTES: u64 = 10 * 1024 * 1024;
+const DEFAULT_MAX_FILES: usize = 5;
+
+/// Longer lines are broken like a terminal would wrap them, so output without newlines can't
+/// grow the line being built without limit
+const MAX_LINE_CHARS: usize = 4096;
+
+/// Colors used for the default foreground/background in HTML logs
+const HTML_FOREGROUND: &str = "#d4d4d4";
+const HTML_BACKGROUND: &str = "#1e1e1e";
+
+const HTML_HEADER: &str = "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<style>\n\
+body { background: #1e1e1e; color: #d4d4d4; margin: 0; }\n\
+pre { font-family: Menlo, Consolas, \"DejaVu Sans Mono\", monospace; font-size: 13px; padding: 12px; margin: 0; white-space: pre-wrap; }\n\
+</style>\n</head>\n<body>\n<pre>";
+const HTML_FOOTER: &str = "</pre>\n</body>\n</html>\n";
+
+/// The 16 ANSI colors of xterm's default palette
+const ANSI_PALETTE: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LogFormat {
+    /// Escape sequences removed, `.log`
+    #[default]
+    Plain,
+    /// Colors and text attributes kept as inline styles, `.html`
+    Html,
+}
+
+/// Options for `start_session_log`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogOptions {
+    pub format: LogFormat,
+    /// Size at which the log is rotated to `<path>.1`
+    pub max_bytes: u64,
+    /// Rotated files kept besides the current one
+    pub max_files: usize,
+}
+
+impl Default for LogOptions {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::Plain,
+            max_bytes: DEFAULT_MAX_BYTES,
+            max_files: DEFAULT_MAX_FILES,
+        }
+    }
+}
+
+/// Converts raw PTY output to log text, one finished line at a time
+/// Carriage returns and backspaces overwrite the current line like a terminal would,
+/// so progress bars end up as their final state instead of every intermediate frame.
+pub struct LogConverter {
+    parser: Parser,
+    lines: LineBuilder,
+}
+
+impl LogConverter {
+    pub fn new(format: LogFormat) -> Self {
+        Self {
+            parser: Parser::new(),
+            lines: LineBuilder {
+                format,
+                line: Vec::new(),
+                col: 0,
+                attrs: CellAttrs::default(),
+                out: String::new(),
+            },
+        }
+    }
+
+    /// Convert output, returns the lines completed by it
+    pub fn convert(&mut self, bytes: &[u8]) -> String {
+        self.parser.advance(&mut self.lines, bytes);
+        std::mem::take(&mut self.lines.out)
+    }
+
+    /// Length of the unfinished last line in cells, without building it
+    pub fn pending_len(&self) -> usize {
+        self.lines.line.len()
+    }
+
+    /// Plain text of the unfinished last line, without trailing blanks
+    pub fn pending_line(&self) -> String {
+        let text: String = self.lines.line.iter().map(|(c, _)| *c).collect();
+        text.trim_end_matches(' ').to_string()
+    }
+
+    /// Return the unfinished last line, if any, and start a new one
+    pub fn flush(&mut self) -> String {
+        if !self.lines.line.is_empty() {
+            self.lines.finish_line(false);
+        }
+        std::mem::take(&mut self.lines.out)
+    }
+}
+
+struct LineBuilder {
+    format: LogFormat,
+    line: Vec<(char, CellAttrs)>,
+    col: usize,
+    attrs: CellAttrs,
+    out: String,
+}
+
+impl LineBuilder {
+    /// Move the cursor without touching the text it passes, like CUF and tabs do
+    fn move_to(&mut self, col: usize) {
+        self.col = col.min(MAX_LINE_CHARS);
+        if self.col > self.line.len() {
+            self.line.resize(self.col, (' ', CellAttrs::default()));
+        }
+    }
+
+    fn finish_line(&mut self, newline: bool) {
+        let line = std::mem::take(&mut self.line);
+        let end = line
+            .iter()
+            .rposition(|(c, _)| *c != ' ')
+            .map_or(0, |last| last + 1);
+
+        match self.format {
+            LogFormat::Plain => self.out.extend(line[..end].iter().map(|(c, _)| *c)),
+            LogFormat::Html => render_html(&line[..end], &mut self.out),
+        }
+        if newline {
+            self.out.push('\n');
+        }
+        self.col = 0;
+    }
+}
+
+impl Perform for LineBuilder {
+    fn print(&mut self, c: char) {
+        if c.width().unwrap_or(0) == 0 {
+            return;
+        }
+        if self.col >= MAX_LINE_CHARS {
+            self.finish_line(true);
+        }
+        if self.col < self.line.len() {
+            self.line[self.col] = (c, self.attrs);
+        } else {
+            self.line.resize(self.col, (' ', CellAttrs::default()));
+            self.line.push((c, self.attrs));
+        }
+        self.col += 1;
+    }
+
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            b'\n' => self.finish_line(true),
+            b'\r' => self.col = 0,
+            0x08 => self.col = self.col.saturating_sub(1),
+            b'\t' => self.move_to((self.col / 8 + 1) * 8),
+            _ => {}
+        }
+    }
+
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], ignore: bool, action: char) {
+        if ignore || !intermediates.is_empty() {
+            return;
+        }
+        let first = params.iter().next().map_or(0, |group| group[0]) as usize;
+        match action {
+            'm' => self.attrs.apply_sgr(params),
+            // Erase to end of line, used by shells when redrawing the command line
+            'K' if first == 0 => self.line.truncate(self.col),
+            'K' if first == 1 => {
+                let end = (self.col + 1).min(self.line.len());
+                self.line[..end].fill((' ', CellAttrs::default()));
+            }
+            'K' if first == 2 => self.line.clear(),
+            'C' => self.move_to(self.col + first.max(1)),
+            'D' => self.col = self.col.saturating_sub(first.max(1)),
+            'G' => self.col = (first.max(1) - 1).min(MAX_LINE_CHARS),
+            _ => {}
+        }
+    }
+}
+
+fn css_color(color: Color) -> Option<String> {
+    let (r, g, b) = match color {
+        Color::Default => return None,
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Indexed(index @ 0..=15) => ANSI_PALETTE[index as usize],
+        Color::Indexed(index @ 16..=231) => {
+            let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+            let index = index - 16;
+            (level(index / 36), level(index / 6 % 6), level(index % 6))
+        }
+        Color::Indexed(index) => {
+            let gray = 8 + (index - 232) * 10;
+            (gray, gray, gray)
+        }
+    };
+    Some(format!("#{:02x}{:02x}{:02x}", r, g, b))
+}
+
+fn css_style(attrs: &CellAttrs) -> String {
+    let (mut fg, mut bg) = (css_color(attrs.fg), css_color(attrs.bg));
+    if attrs.inverse {
+        let swapped_fg = bg.unwrap_or_else(|| HTML_BACKGROUND.to_string());
+        let swapped_bg = fg.unwrap_or_else(|| HTML_FOREGROUND.to_string());
+        fg = Some(swapped_fg);
+        bg = Some(swapped_bg);
+    }
+
+    let mut style = String::new();
+    if let Some(fg) = fg {
+        let _ = write!(style, "color:{};", fg);
+    }
+    if let Some(bg) = bg {
+        let _ = write!(style, "background:{};", bg);
+    }
+    if attrs.bold {
+        style.push_str("font-weight:bold;");
+    }
+    if attrs.dim {
+        style.push_str("opacity:0.6;");
+    }
+    if attrs.italic {
+        style.push_str("font-style:italic;");
+    }
+    match (attrs.underline, attrs.strikethrough) {
+        (true, true) => style.push_str("text-decoration:underline line-through;"),
+        (true, false) => style.push_str("text-decoration:underline;"),
+        (false, true) => style.push_str("text-decoration:line-through;"),
+        (false, false) => {}
+    }
+    if attrs.hidden {
+        style.push_str("visibility:hidden;");
+    }
+    style
+}
+
+fn escape_html(c: char, out: &mut String) {
+    match c {
+        '&' => out.push_str("&amp;"),
+        '<' => out.push_str("&lt;"),
+        '>' => out.push_str("&gt;"),
+        '"' => out.push_str("&quot;"),
+        c => out.push(c),
+    }
+}
+
+/// Render a line as runs of equally styled text
+fn render_html(line: &[(char, CellAttrs)], out: &mut String) {
+    let mut index = 0;
+    while index < line.len() {
+        let attrs = line[index].1;
+        let run_end = line[index..]
+            .iter()
+            .position(|(_, other)| *other != attrs)
+            .map_or(line.len(), |offset| index + offset);
+
+        let style = css_style(&attrs);
+        if !style.is_empty() {
+            let _ = write!(out, "<span style=\"{}\">", style);
+        }
+        for (c, _) in &line[index..run_end] {
+            escape_html(*c, out);
+        }
+        if !style.is_empty() {
+            out.push_str("</span>");
+        }
+        index = run_end;
+    }
+}
+
+/// Convert a complete chunk of output, e.g. a scrollback snapshot, to a log document
+pub fn convert_log(data: &[u8], format: LogFormat) -> String {
+    let mut converter = LogConverter::new(format);
+    let mut text = converter.convert(data);
+    text.push_str(&converter.flush());
+    match format {
+        LogFormat::Plain => text,
+        LogFormat::Html => format!("{}{}{}", HTML_HEADER, text, HTML_FOOTER),
+    }
+}
+
+/// Shift `<path>.N` to `<path>.N+1`, dropping the oldest, and move `path` to `<path>.1`
+fn rotate_files(path: &Path, max_files: usize) -> std::io::Result<()> {
+    let rotated = |n: usize| PathBuf::from(format!("{}.{}", path.display(), n));
+    if max_files == 0 {
+        return fs::remove_file(path);
+    }
+    let _ = fs::remove_file(rotated(max_files));
+    for n in (1..max_files).rev() {
+        let from = rotated(n);
+        if from.exists() {
+            fs::rename(&from, rotated(n + 1))?;
+        }
+    }
+    fs::rename(path, rotated(1))
+}
+
+/// Writes a session's output to a log file, rotating it once it grows past `max_bytes`
+pub struct SessionLogger {
+    path: PathBuf,
+    options: LogOptions,
+    converter: LogConverter,
+    writer: BufWriter<File>,
+    written: u64,
+}
+
+impl SessionLogger {
+    /// Plain logs are appended to, an existing HTML log is rotated away first because
+    /// nothing can be appended after its closing tags
+    pub fn create(path: &Path, options: LogOptions) -> std::io::Result<Self> {
+        let existing = fs::metadata(path).map_or(0, |metadata| metadata.len());
+        if options.format == LogFormat::Html && existing > 0 {
+            rotate_files(path, options.max_files)?;
+        }
+        let (writer, written) = Self::open(path, options.format)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            converter: LogConverter::new(options.format),
+            options,
+            writer,
+            written,
+        })
+    }
+
+    /// Append to the log at `path`, an empty HTML log gets the document header
+    fn open(path: &Path, format: LogFormat) -> std::io::Result<(BufWriter<File>, u64)> {
+        let file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+        let mut written = file.metadata()?.len();
+        let mut writer = BufWriter::new(file);
+        if format == LogFormat::Html && written == 0 {
+            writer.write_all(HTML_HEADER.as_bytes())?;
+            written += HTML_HEADER.len() as u64;
+        }
+        Ok((writer, written))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let text = self.converter.convert(data);
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.writer.write_all(text.as_bytes())?;
+        // Flush per chunk so `tail -f` on the log keeps up
+        self.writer.flush()?;
+        self.written += text.len() as u64;
+
+        if self.options.max_bytes > 0 && self.written >= self.options.max_bytes {
+            self.rotate()?;
+        }
+        Ok(())
+    }
+
+    /// Shift `<path>.N` to `<path>.N+1`, dropping the oldest, and start a new file
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.close_document()?;
+        rotate_files(&self.path, self.options.max_files)?;
+
+        let (writer, written) = Self::open(&self.path, self.options.format)?;
+        self.writer = writer;
+        self.written = written;
+        Ok(())
+    }
+
+    fn close_document(&mut self) -> std::io::Result<()> {
+        if self.options.format == LogFormat::Html {
+            self.writer.write_all(HTML_FOOTER.as_bytes())?;
+        }
+        self.writer.flush()
+    }
+
+    /// Write the unfinished last line and close the file
+    pub fn finish(mut self) -> std::io::Result<()> {
+        let text = self.converter.flush();
+        self.writer.write_all(text.as_bytes())?;
+        self.close_document()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_lines_are_broken() {
+        let mut converter = LogConverter::new(LogFormat::Plain);
+        let text = converter.convert("x".repeat(MAX_LINE_CHARS + 10).as_bytes());
+        assert_eq!(text.len(), MAX_LINE_CHARS + 1);
+        assert_eq!(converter.pending_len(), 10);
+
+        // Moving the cursor far right can't grow the line past the limit either
+        converter.convert(b"\r\x1b[99999999Cy");
+        assert_eq!(converter.pending_len(), 1);
+    }
+
+    #[test]
+    fn erase_in_line() {
+        let mut converter = LogConverter::new(LogFormat::Plain);
+        // To the end, to the start including the cursor, and the whole line
+        let text = converter.convert(b"abcdef\x1b[3D\x1b[K\r\n");
+        let text = text + &converter.convert(b"abcdef\x1b[3D\x1b[1K\r\n");
+        let text = text + &converter.convert(b"abcdef\x1b[2K\rxy\r\n");
+        assert_eq!(text, "abc\n    ef\nxy\n");
+    }
+}
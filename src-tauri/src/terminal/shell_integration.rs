@@ -0,0 +1,218 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/// Startup files that chain to the user's own files, ZDOTDIR points here
+const ZSH_FILES: &[(&str, &str)] = &[
+    (".zshenv", include_str!("../../shell-integration/zsh/.zshenv")),
+    (".zprofile", include_str!("../../shell-integration/zsh/.zprofile")),
+    (".zshrc", include_str!("../../shell-integration/zsh/.zshrc")),
+    (".zlogin", include_str!("../../shell-integration/zsh/.zlogin")),
+    ("tipterm.zsh", include_str!("../../shell-integration/zsh/tipterm.zsh")),
+];
+
+/// Passed to bash with `--init-file`, loads the usual profile files itself
+const BASH_INIT_FILE: (&str, &str) = (
+    "tipterm.bash",
+    include_str!("../../shell-integration/bash/tipterm.bash"),
+);
+
+/// Sourced by fish with `--init-command` after its own config files
+const FISH_INIT_FILE: (&str, &str) = (
+    "tipterm.fish",
+    include_str!("../../shell-integration/fish/tipterm.fish"),
+);
+
+/// Command line submitted, sent as `terminal-command-started`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandStarted {
+    /// Command text, None if the shell did not report it and it could not be read from the screen
+    pub command: Option<String>,
+    pub cwd: Option<String>,
+    /// Unix timestamp in milliseconds
+    pub started_at: u64,
+}
+
+/// Command completed, sent as `terminal-command-finished`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandFinished {
+    pub command: Option<String>,
+    pub cwd: Option<String>,
+    pub exit_code: Option<i32>,
+    /// Unix timestamp in milliseconds
+    pub started_at: u64,
+    pub duration_ms: u64,
+}
+
+/// Event derived from OSC 133 / OSC 7 sequences in a session's output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ShellEvent {
+    CommandStarted(CommandStarted),
+    CommandFinished(CommandFinished),
+    CwdChanged { cwd: String },
+}
+
+struct RunningCommand {
+    command: Option<String>,
+    cwd: Option<String>,
+    started: Instant,
+    started_at: u64,
+}
+
+/// Command boundaries and cwd reported by the shell
+/// Marks: `133;A` prompt start, `133;B` command input start, `133;C` command executed
+/// (optionally with `cmdline_url=`), `133;D;<exit>` command finished. OSC 7 reports the cwd.
+#[derive(Default)]
+pub struct ShellIntegration {
+    cwd: Option<String>,
+    /// Absolute line and column where the command line starts
+    input_start: Option<(u64, usize)>,
+    running: Option<RunningCommand>,
+    events: Vec<ShellEvent>,
+}
+
+impl ShellIntegration {
+    /// Last cwd reported through OSC 7
+    pub fn cwd(&self) -> Option<&str> {
+        self.cwd.as_deref()
+    }
+
+    pub fn is_command_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    pub fn take_events(&mut self) -> Vec<ShellEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub(crate) fn prompt_started(&mut self) {
+        // A new prompt without a D mark means the previous command was interrupted
+        // or the shell doesn't report exit codes
+        self.finish_command(None);
+        self.input_start = None;
+    }
+
+    pub(crate) fn input_started(&mut self, line: u64, col: usize) {
+        self.input_start = Some((line, col));
+    }
+
+    pub(crate) fn input_start(&self) -> Option<(u64, usize)> {
+        self.input_start
+    }
+
+    pub(crate) fn command_executed(&mut self, command: Option<String>) {
+        self.finish_command(None);
+        self.input_start = None;
+
+        let started_at = unix_millis();
+        self.events.push(ShellEvent::CommandStarted(CommandStarted {
+            command: command.clone(),
+            cwd: self.cwd.clone(),
+            started_at,
+        }));
+        self.running = Some(RunningCommand {
+            command,
+            cwd: self.cwd.clone(),
+            started: Instant::now(),
+            started_at,
+        });
+    }
+
+    pub(crate) fn finish_command(&mut self, exit_code: Option<i32>) {
+        let Some(running) = self.running.take() else {
+            return;
+        };
+        self.events.push(ShellEvent::CommandFinished(CommandFinished {
+            command: running.command,
+            cwd: running.cwd,
+            exit_code,
+            started_at: running.started_at,
+            duration_ms: running.started.elapsed().as_millis() as u64,
+        }));
+    }
+
+    pub(crate) fn set_cwd(&mut self, cwd: String) {
+        if self.cwd.as_deref() != Some(cwd.as_str()) {
+            self.events.push(ShellEvent::CwdChanged { cwd: cwd.clone() });
+            self.cwd = Some(cwd);
+        }
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Decode `%XX` escapes, invalid escapes are kept as is
+pub fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Path of an OSC 7 `file://host/path` URL
+pub fn parse_cwd_url(url: &str) -> Option<String> {
+    let rest = url.strip_prefix("file://")?;
+    // Skip the host, the path starts at the first slash
+    let path = &rest[rest.find('/')?..];
+    Some(percent_decode(path))
+}
+
+/// Write the integration scripts to `dir`, skipping files that are already up to date
+fn install_files(dir: &Path, files: &[(&str, &str)]) -> std::io::Result<()> {
+    // The shell runs these files, refuse a directory someone else could write to
+    super::ensure_private_dir(dir)?;
+    for (name, content) in files {
+        let path = dir.join(name);
+        if fs::read_to_string(&path).is_ok_and(|existing| existing == *content) {
+            continue;
+        }
+        // Another instance may be starting a shell from this file right now
+        let tmp_path = dir.join(format!("{}.{}.tmp", name, std::process::id()));
+        fs::write(&tmp_path, content)?;
+        fs::rename(&tmp_path, &path)?;
+    }
+    Ok(())
+}
+
+/// Install the zsh scripts, returns the directory to use as ZDOTDIR
+pub fn install_zsh_scripts() -> std::io::Result<PathBuf> {
+    let dir = super::runtime_dir()?.join("shell-integration").join("zsh");
+    install_files(&dir, ZSH_FILES)?;
+    Ok(dir)
+}
+
+/// Install the bash script, returns the path to pass to `--init-file`
+pub fn install_bash_script() -> std::io::Result<PathBuf> {
+    let dir = super::runtime_dir()?.join("shell-integration").join("bash");
+    install_files(&dir, &[BASH_INIT_FILE])?;
+    Ok(dir.join(BASH_INIT_FILE.0))
+}
+
+/// Install the fish script, returns the path to source with `--init-command`
+pub fn install_fish_script() -> std::io::Result<PathBuf> {
+    let dir = super::runtime_dir()?.join("shell-integration").join("fish");
+    install_files(&dir, &[FISH_INIT_FILE])?;
+    Ok(dir.join(FISH_INIT_FILE.0))
+}
@@ -0,0 +1,298 @@
+use std::ffi::OsString;
+use std::path::Path;
+
+use super::shell_integration;
+use super::spec::{SessionKind, SessionSpec};
+
+/// Shells whose login and integration flags are known
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellKind {
+    Zsh,
+    Bash,
+    Fish,
+    Nushell,
+    Xonsh,
+    Dash,
+    /// sh, ksh and other POSIX shells that take `-l`
+    Posix,
+    /// tcsh and csh, which only accept `-l` as their sole argument
+    Csh,
+    Pwsh,
+    /// Anything else, started without extra flags
+    Other,
+}
+
+impl ShellKind {
+    /// Detect the shell from the program's file name, e.g. `/usr/bin/zsh` or `pwsh.exe`
+    pub fn detect(program: &str) -> Self {
+        let name = Path::new(program)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let name = name.strip_suffix(".exe").unwrap_or(&name);
+        // Login shells show up as `-zsh` in some places
+        match name.trim_start_matches('-') {
+            "zsh" => Self::Zsh,
+            "bash" => Self::Bash,
+            "fish" => Self::Fish,
+            "nu" => Self::Nushell,
+            "xonsh" => Self::Xonsh,
+            "dash" => Self::Dash,
+            "sh" | "ksh" | "mksh" | "oksh" | "yash" => Self::Posix,
+            "tcsh" | "csh" => Self::Csh,
+            "pwsh" => Self::Pwsh,
+            _ => Self::Other,
+        }
+    }
+
+    /// Flag that starts the shell as a login shell, so its profile files are loaded
+    pub fn login_flag(self) -> Option<&'static str> {
+        match self {
+            Self::Zsh | Self::Bash | Self::Fish | Self::Dash | Self::Posix | Self::Csh => {
+                Some("-l")
+            }
+            Self::Nushell | Self::Xonsh => Some("--login"),
+            Self::Pwsh => Some("-Login"),
+            Self::Other => None,
+        }
+    }
+}
+
+/// Program, arguments and extra environment a session is spawned with
+#[derive(Debug, Clone)]
+pub struct ShellLaunch {
+    pub program: String,
+    pub args: Vec<OsString>,
+    /// Set by shell integration, goes before the caller's environment
+    pub env: Vec<(&'static str, String)>,
+    /// Value for SHELL: the shell itself, or the user's shell for commands
+    pub shell_env: String,
+}
+
+/// Work out what to spawn for `shell` and `spec`
+/// An empty `shell` starts the user's shell. Shells get the login flag first, which pwsh
+/// requires, then the integration flags and `spec.args`; commands get `spec.args` only.
+pub fn resolve(shell: &str, spec: &SessionSpec) -> std::io::Result<ShellLaunch> {
+    let shell = shell.trim();
+    if spec.kind == SessionKind::Command {
+        if shell.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "No program given for command session",
+            ));
+        }
+        return Ok(ShellLaunch {
+            program: shell.to_string(),
+            args: spec.args.iter().map(OsString::from).collect(),
+            env: Vec::new(),
+            shell_env: default_shell(),
+        });
+    }
+
+    let program = if shell.is_empty() {
+        default_shell()
+    } else {
+        shell.to_string()
+    };
+    let kind = ShellKind::detect(&program);
+    let unix = cfg!(any(target_os = "macos", target_os = "linux"));
+    let mut login = unix && spec.login;
+    let mut integration_args: Vec<OsString> = Vec::new();
+    let mut env = Vec::new();
+
+    // Shell integration is injected only into plain interactive shells, extra
+    // arguments (e.g. `-c`) could conflict with the flags used to load the scripts
+    if unix && spec.shell_integration && spec.args.is_empty() {
+        match kind {
+            ShellKind::Zsh => match shell_integration::install_zsh_scripts() {
+                Ok(dir) => {
+                    let user_zdotdir = std::env::var("ZDOTDIR")
+                        .or_else(|_| std::env::var("HOME"))
+                        .unwrap_or_default();
+                    env.push(("TIPTERM_USER_ZDOTDIR", user_zdotdir));
+                    env.push(("ZDOTDIR", dir.to_string_lossy().to_string()));
+                }
+                Err(e) => eprintln!("Failed to install zsh integration: {}", e),
+            },
+            ShellKind::Bash => match shell_integration::install_bash_script() {
+                Ok(path) => {
+                    // The init file loads the profile files itself when asked to
+                    if login {
+                        env.push(("TIPTERM_BASH_LOGIN", "1".to_string()));
+                        login = false;
+                    }
+                    integration_args.push("--init-file".into());
+                    integration_args.push(path.into_os_string());
+                }
+                Err(e) => eprintln!("Failed to install bash integration: {}", e),
+            },
+            ShellKind::Fish => match shell_integration::install_fish_script() {
+                Ok(path) => {
+                    let source = format!("source {}", fish_quote(&path.to_string_lossy()));
+                    integration_args.push("--init-command".into());
+                    integration_args.push(source.into());
+                }
+                Err(e) => eprintln!("Failed to install fish integration: {}", e),
+            },
+            _ => {}
+        }
+    }
+
+    // csh refuses `-l` next to other arguments, start it as a plain shell then
+    if kind == ShellKind::Csh && !spec.args.is_empty() {
+        login = false;
+    }
+
+    let mut args = Vec::new();
+    if login {
+        args.extend(kind.login_flag().map(OsString::from));
+    }
+    args.extend(integration_args);
+    args.extend(spec.args.iter().map(OsString::from));
+
+    Ok(ShellLaunch {
+        shell_env: program.clone(),
+        program,
+        args,
+        env,
+    })
+}
+
+/// The user's shell from SHELL or the passwd entry, /bin/sh if neither is set
+#[cfg(unix)]
+pub fn default_shell() -> String {
+    std::env::var("SHELL")
+        .ok()
+        .filter(|shell| !shell.trim().is_empty())
+        .or_else(passwd_shell)
+        .unwrap_or_else(|| "/bin/sh".to_string())
+}
+
+/// The command interpreter from COMSPEC
+#[cfg(not(unix))]
+pub fn default_shell() -> String {
+    std::env::var("COMSPEC").unwrap_or_else(|_| "cmd.exe".to_string())
+}
+
+#[cfg(unix)]
+fn passwd_shell() -> Option<String> {
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+    let mut buffer = vec![0 as libc::c_char; 4096];
+    let status = unsafe {
+        libc::getpwuid_r(
+            libc::getuid(),
+            &mut passwd,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+    if status != 0 || result.is_null() || passwd.pw_shell.is_null() {
+        return None;
+    }
+    let shell = unsafe { std::ffi::CStr::from_ptr(passwd.pw_shell) };
+    let shell = shell.to_str().ok()?;
+    (!shell.is_empty()).then(|| shell.to_string())
+}
+
+/// Single-quote `value` for fish, where only `\` and `'` are special inside quotes
+fn fish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shell_spec(args: &[&str]) -> SessionSpec {
+        SessionSpec {
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            shell_integration: false,
+            ..SessionSpec::default()
+        }
+    }
+
+    #[test]
+    fn detect_by_file_name() {
+        assert_eq!(ShellKind::detect("/usr/bin/zsh"), ShellKind::Zsh);
+        assert_eq!(ShellKind::detect("-bash"), ShellKind::Bash);
+        assert_eq!(ShellKind::detect("/opt/homebrew/bin/fish"), ShellKind::Fish);
+        assert_eq!(ShellKind::detect("nu"), ShellKind::Nushell);
+        assert_eq!(ShellKind::detect("/bin/sh"), ShellKind::Posix);
+        assert_eq!(ShellKind::detect("/bin/tcsh"), ShellKind::Csh);
+        assert_eq!(ShellKind::detect("csh"), ShellKind::Csh);
+        assert_eq!(ShellKind::detect("PWSH.EXE"), ShellKind::Pwsh);
+    }
+
+    #[test]
+    fn detect_does_not_match_name_suffixes() {
+        assert_eq!(ShellKind::detect("/usr/bin/ssh"), ShellKind::Other);
+        assert_eq!(ShellKind::detect("/usr/local/bin/mosh"), ShellKind::Other);
+        assert_eq!(ShellKind::detect(""), ShellKind::Other);
+    }
+
+    #[test]
+    fn ssh_session_gets_no_login_flag() {
+        let launch = resolve("/usr/bin/ssh", &shell_spec(&["host"])).unwrap();
+        assert_eq!(launch.program, "/usr/bin/ssh");
+        assert_eq!(launch.args, vec![OsString::from("host")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn empty_shell_starts_the_default_shell() {
+        let launch = resolve("  ", &shell_spec(&[])).unwrap();
+        assert_eq!(launch.program, default_shell());
+        assert!(!launch.program.is_empty());
+        assert_eq!(launch.shell_env, launch.program);
+    }
+
+    #[test]
+    fn empty_command_is_an_error() {
+        let spec = SessionSpec {
+            kind: SessionKind::Command,
+            ..shell_spec(&["-la"])
+        };
+        let error = resolve("", &spec).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn command_gets_its_args_only() {
+        let spec = SessionSpec {
+            kind: SessionKind::Command,
+            ..shell_spec(&["-la"])
+        };
+        let launch = resolve("/bin/bash", &spec).unwrap();
+        assert_eq!(launch.program, "/bin/bash");
+        assert_eq!(launch.args, vec![OsString::from("-la")]);
+        assert!(launch.env.is_empty());
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    #[test]
+    fn pwsh_login_flag_comes_first() {
+        let launch = resolve("/usr/bin/pwsh", &shell_spec(&["-NoLogo"])).unwrap();
+        assert_eq!(
+            launch.args,
+            vec![OsString::from("-Login"), OsString::from("-NoLogo")]
+        );
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    #[test]
+    fn login_flags() {
+        let args = |shell: &str, extra: &[&str]| resolve(shell, &shell_spec(extra)).unwrap().args;
+        assert_eq!(args("/bin/zsh", &[]), vec![OsString::from("-l")]);
+        assert_eq!(args("/usr/bin/xonsh", &[]), vec![OsString::from("--login")]);
+        assert_eq!(args("/bin/tcsh", &[]), vec![OsString::from("-l")]);
+        assert_eq!(args("/bin/tcsh", &["-f"]), vec![OsString::from("-f")]);
+        let no_login = SessionSpec {
+            login: false,
+            ..shell_spec(&[])
+        };
+        assert!(resolve("/bin/zsh", &no_login).unwrap().args.is_empty());
+    }
+}
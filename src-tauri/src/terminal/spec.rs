@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::scrollback::DEFAULT_SCROLLBACK_BYTES;
+use super::triggers::TriggerConfig;
+
+const DEFAULT_COLS: u16 = 80;
+const DEFAULT_ROWS: u16 = 24;
+
+/// What a session runs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SessionKind {
+    /// An interactive shell, the user's login shell when none is given
+    #[default]
+    Shell,
+    /// A program run directly with `args`, without login or integration flags
+    Command,
+}
+
+/// Options for spawning a terminal session
+/// Every field is optional on the wire, missing fields fall back to the defaults below.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SessionSpec {
+    /// Whether the session's program is a shell or a plain command
+    pub kind: SessionKind,
+    /// Initial PTY width, should match the frontend's fitted size to avoid a resize flash
+    pub cols: u16,
+    /// Initial PTY height
+    pub rows: u16,
+    /// Starting directory, `~` is expanded. Falls back to HOME if it is not a directory
+    pub cwd: Option<String>,
+    /// Extra environment variables, these override the session defaults (TERM, LANG, ...)
+    pub env: HashMap<String, String>,
+    /// Environment variables to remove from the inherited environment
+    pub env_remove: Vec<String>,
+    /// Extra arguments appended after the login flag, or all arguments of a command
+    pub args: Vec<String>,
+    /// Start Unix shells as login shells so profile files are loaded
+    pub login: bool,
+    /// Bytes of recent output kept in the backend for `get_session_snapshot`
+    pub scrollback_bytes: usize,
+    /// Load the zsh/bash/fish integration scripts that report command boundaries and cwd
+    pub shell_integration: bool,
+    /// Encoding for text written with `write_to_session`, a WHATWG label such as
+    /// "shift_jis" or "windows-1252". None means UTF-8
+    pub input_encoding: Option<String>,
+    /// Output triggers, the app fills these in from the terminal config
+    pub triggers: Vec<TriggerConfig>,
+}
+
+impl Default for SessionSpec {
+    fn default() -> Self {
+        Self {
+            kind: SessionKind::default(),
+            cols: DEFAULT_COLS,
+            rows: DEFAULT_ROWS,
+            cwd: None,
+            env: HashMap::new(),
+            env_remove: Vec::new(),
+            args: Vec::new(),
+            login: true,
+            scrollback_bytes: DEFAULT_SCROLLBACK_BYTES,
+            shell_integration: true,
+            input_encoding: None,
+            triggers: Vec::new(),
+        }
+    }
+}
+
+impl SessionSpec {
+    /// Starting directory with a leading `~` expanded to HOME
+    pub fn resolved_cwd(&self) -> Option<String> {
+        let cwd = self.cwd.as_deref()?.trim();
+        if cwd.is_empty() {
+            return None;
+        }
+        if cwd == "~" || cwd.starts_with("~/") {
+            if let Ok(home) = std::env::var("HOME") {
+                return Some(format!("{}{}", home, &cwd[1..]));
+            }
+        }
+        Some(cwd.to_string())
+    }
+}
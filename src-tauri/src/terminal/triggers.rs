@@ -0,0 +1,261 @@
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use super::session_log::{LogConverter, LogFormat};
+
+/// Instant triggers skip an unfinished line longer than this, it is matched again on every
+/// read and prompts are much shorter
+const MAX_INSTANT_LINE_CHARS: usize = 1024;
+
+/// A `Respond` trigger is skipped this long after it fired, so a response that the program
+/// echoes back or that makes it print the prompt again can't turn into a loop
+const RESPOND_COOLDOWN: Duration = Duration::from_secs(1);
+
+/// What a trigger does when its pattern matches
+/// `$0`, `$1`, `${name}` in text fields are replaced with the match and its groups.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum TriggerAction {
+    /// Ask the frontend to highlight the match
+    Highlight {
+        #[serde(default)]
+        color: Option<String>,
+    },
+    /// Show a desktop notification, the body defaults to the matched line
+    Notify {
+        #[serde(default)]
+        title: Option<String>,
+        #[serde(default)]
+        body: Option<String>,
+    },
+    /// Write text to the session, e.g. to answer a known prompt, at most once per second
+    Respond { text: String },
+    /// Ask the frontend to mark the session's tab
+    MarkTab,
+}
+
+/// A trigger as written in the config file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TriggerConfig {
+    /// Regex matched against each line of output, escape sequences removed
+    pub pattern: String,
+    pub action: TriggerAction,
+    /// Also match the unfinished last line, for prompts that wait on the same line
+    #[serde(default)]
+    pub instant: bool,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl TriggerConfig {
+    /// Compile the pattern, fails with a message naming it
+    pub fn compile(&self) -> Result<Regex, String> {
+        Regex::new(&self.pattern)
+            .map_err(|e| format!("Invalid trigger pattern {:?}: {}", self.pattern, e))
+    }
+}
+
+/// A trigger that fired, sent to the app which carries out the action
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TriggerEvent {
+    /// Index of the trigger in the configured list
+    pub trigger: usize,
+    /// Character range of the match within `line_text`
+    pub start: usize,
+    pub end: usize,
+    pub matched: String,
+    pub line_text: String,
+    /// The trigger's action with `$n` references expanded
+    pub action: TriggerAction,
+}
+
+struct Trigger {
+    index: usize,
+    regex: Regex,
+    action: TriggerAction,
+    instant: bool,
+    /// When the trigger last fired, only kept for `Respond`
+    last_response: Option<Instant>,
+}
+
+/// Matches a session's output against its triggers, line by line
+pub struct TriggerMatcher {
+    triggers: Vec<Trigger>,
+    converter: LogConverter,
+    /// Triggers that already fired on the current line, so instant ones fire once per line
+    fired: Vec<usize>,
+}
+
+impl Default for TriggerMatcher {
+    fn default() -> Self {
+        Self {
+            triggers: Vec::new(),
+            converter: LogConverter::new(LogFormat::Plain),
+            fired: Vec::new(),
+        }
+    }
+}
+
+impl TriggerMatcher {
+    /// Replace the triggers, fails on the first invalid pattern
+    pub fn set_triggers(&mut self, configs: &[TriggerConfig]) -> Result<(), String> {
+        let mut triggers = Vec::new();
+        for (index, config) in configs.iter().enumerate() {
+            if !config.enabled {
+                continue;
+            }
+            triggers.push(Trigger {
+                index,
+                regex: config.compile()?,
+                action: config.action.clone(),
+                instant: config.instant,
+                last_response: None,
+            });
+        }
+        if self.triggers.is_empty() {
+            // Output wasn't converted while there were no triggers
+            self.converter = LogConverter::new(LogFormat::Plain);
+        }
+        self.triggers = triggers;
+        self.fired.clear();
+        Ok(())
+    }
+
+    /// Match newly received output, returns the triggers that fired
+    pub fn process(&mut self, data: &[u8]) -> Vec<TriggerEvent> {
+        let mut events = Vec::new();
+        if self.triggers.is_empty() {
+            return events;
+        }
+
+        let lines = self.converter.convert(data);
+        for line in lines.lines() {
+            self.match_line(line, false, &mut events);
+            self.fired.clear();
+        }
+
+        let instant = self.triggers.iter().any(|trigger| trigger.instant);
+        if instant && self.converter.pending_len() <= MAX_INSTANT_LINE_CHARS {
+            let pending = self.converter.pending_line();
+            if !pending.is_empty() {
+                self.match_line(&pending, true, &mut events);
+            }
+        }
+        events
+    }
+
+    fn match_line(&mut self, text: &str, pending: bool, events: &mut Vec<TriggerEvent>) {
+        for trigger in &mut self.triggers {
+            if (pending && !trigger.instant) || self.fired.contains(&trigger.index) {
+                continue;
+            }
+            let cooling_down = trigger
+                .last_response
+                .is_some_and(|last| last.elapsed() < RESPOND_COOLDOWN);
+            if cooling_down {
+                continue;
+            }
+            let Some(captures) = trigger.regex.captures(text) else {
+                continue;
+            };
+            let whole = captures.get(0).expect("group 0 is the whole match");
+            self.fired.push(trigger.index);
+            if matches!(trigger.action, TriggerAction::Respond { .. }) {
+                trigger.last_response = Some(Instant::now());
+            }
+            events.push(TriggerEvent {
+                trigger: trigger.index,
+                start: text[..whole.start()].chars().count(),
+                end: text[..whole.end()].chars().count(),
+                matched: whole.as_str().to_string(),
+                line_text: text.to_string(),
+                action: expand_action(&trigger.action, &captures),
+            });
+        }
+    }
+}
+
+fn expand_action(action: &TriggerAction, captures: &regex::Captures) -> TriggerAction {
+    let expand = |template: &str| {
+        let mut out = String::new();
+        captures.expand(template, &mut out);
+        out
+    };
+    match action {
+        TriggerAction::Notify { title, body } => TriggerAction::Notify {
+            title: title.as_deref().map(expand),
+            body: body.as_deref().map(expand),
+        },
+        TriggerAction::Respond { text } => TriggerAction::Respond { text: expand(text) },
+        action => action.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(pattern: &str, instant: bool) -> TriggerMatcher {
+        matcher_with_action(pattern, instant, TriggerAction::MarkTab)
+    }
+
+    fn matcher_with_action(pattern: &str, instant: bool, action: TriggerAction) -> TriggerMatcher {
+        let mut matcher = TriggerMatcher::default();
+        let config = TriggerConfig {
+            pattern: pattern.to_string(),
+            action,
+            instant,
+            enabled: true,
+        };
+        matcher.set_triggers(&[config]).unwrap();
+        matcher
+    }
+
+    #[test]
+    fn matches_completed_lines() {
+        let mut matcher = matcher("error: (\\w+)", false);
+        assert!(matcher.process(b"\x1b[31merror: ").is_empty());
+        let events = matcher.process(b"failed\x1b[0m\r\nok\r\n");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].line_text, "error: failed");
+        assert_eq!((events[0].start, events[0].end), (0, 13));
+    }
+
+    #[test]
+    fn instant_trigger_fires_once_per_line() {
+        let mut matcher = matcher("[Pp]assword:", true);
+        assert_eq!(matcher.process(b"Password: ").len(), 1);
+        assert!(matcher.process(b"***").is_empty());
+        assert!(matcher.process(b"\r\n").is_empty());
+        assert_eq!(matcher.process(b"password:").len(), 1);
+    }
+
+    #[test]
+    fn instant_trigger_skips_long_unfinished_lines() {
+        let mut matcher = matcher("x$", true);
+        let long = "x".repeat(MAX_INSTANT_LINE_CHARS + 1);
+        assert!(matcher.process(long.as_bytes()).is_empty());
+        // The whole line is still matched once it ends
+        assert_eq!(matcher.process(b"\r\n").len(), 1);
+    }
+
+    #[test]
+    fn respond_trigger_cools_down() {
+        let respond = TriggerAction::Respond { text: "yes\n".to_string() };
+        let mut matcher = matcher_with_action("Continue\\?", true, respond);
+        assert_eq!(matcher.process(b"Continue? ").len(), 1);
+        // The prompt printed again right after the response
+        assert!(matcher.process(b"yes\r\nContinue? ").is_empty());
+
+        matcher.triggers[0].last_response = Some(Instant::now() - RESPOND_COOLDOWN);
+        assert_eq!(matcher.process(b"\r\nContinue? ").len(), 1);
+    }
+}
@@ -1,57 +1,223 @@
-use std::io::{Read, Write};
-use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use portable_pty::{native_pty_system, CommandBuilder, MasterPty, PtySize};
 use serde::{Deserialize, Serialize};
-use sysinfo::{ProcessesToUpdate, System};
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+use tokio::sync::mpsc;
 
-/// Type alias for PTY writer that can be shared across threads
-pub type PtyWriter = Arc<Mutex<Box<dyn Write + Send>>>;
+use super::encoding::InputEncoding;
+use super::flow_control::FlowControl;
+use super::metrics::SessionMetrics;
+use super::recording::{Recorder, RecordingInfo};
+use super::screen::{ScreenSnapshot, TerminalScreen, DEFAULT_SCROLLBACK_LINES};
+use super::scrollback::{ScrollbackBuffer, ScrollbackSnapshot};
+use super::session_log::{LogOptions, SessionLogger};
+use super::shell_integration::ShellEvent;
+use super::shell_profile;
+use super::spec::{SessionKind, SessionSpec};
+use super::triggers::{TriggerConfig, TriggerEvent, TriggerMatcher};
+use super::writer::PtyWriter;
+
+/// Receiving end of a session's reader thread, yields raw PTY output chunks
+pub type PtyOutputReceiver = mpsc::Receiver<Vec<u8>>;
+
+/// Command boundaries and cwd changes reported through shell integration
+pub type ShellEventReceiver = mpsc::UnboundedReceiver<ShellEvent>;
+
+/// Output triggers that matched, the app carries out their actions
+pub type TriggerEventReceiver = mpsc::UnboundedReceiver<TriggerEvent>;
+
+/// Foreground process changes, see `watch_foreground_process`
+pub type ProcessChangeReceiver = mpsc::UnboundedReceiver<ProcessInfo>;
+
+/// Size of a single blocking read from the PTY
+const READ_CHUNK_SIZE: usize = 8192;
+
+/// Number of chunks the reader thread may queue before it blocks
+const OUTPUT_CHANNEL_CAPACITY: usize = 64;
+
+/// How often `watch_foreground_process` checks a session's foreground process
+const PROCESS_WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Process table shared by all lookups, refreshed one PID at a time
+static PROCESSES: OnceLock<Mutex<System>> = OnceLock::new();
 
 /// Process information for a terminal session
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ProcessInfo {
     pub name: String,
     pub cwd: String,
+    pub pid: u32,
+    /// Full command line, just the name if it can't be read
+    pub argv: Vec<String>,
+    /// Seconds since the Unix epoch
+    pub start_time: u64,
+    /// CPU usage since the previous lookup, 100 per fully used core
+    pub cpu_percent: f32,
+    /// Resident set size
+    pub rss_bytes: u64,
+    /// True when no job is running in the foreground and this is the session's shell
+    pub is_shell: bool,
+}
+
+impl ProcessInfo {
+    /// Same process in the same directory, ignoring resource usage
+    pub fn same_process(&self, other: &ProcessInfo) -> bool {
+        self.pid == other.pid && self.name == other.name && self.cwd == other.cwd
+    }
+}
+
+/// A process and its descendants, see `get_session_process_tree`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessNode {
+    #[serde(flatten)]
+    pub info: ProcessInfo,
+    pub children: Vec<ProcessNode>,
+}
+
+/// Exit status of a session's child process, sent with the `terminal-exit-{id}` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionExitStatus {
+    /// Exit code, None when the process was terminated by a signal
+    pub code: Option<u32>,
+    /// Description of the signal that terminated the process
+    pub signal: Option<String>,
+    pub success: bool,
+}
+
+impl From<portable_pty::ExitStatus> for SessionExitStatus {
+    fn from(status: portable_pty::ExitStatus) -> Self {
+        // portable-pty only exposes the signal through its Display impl
+        let signal = status
+            .to_string()
+            .strip_prefix("Terminated by ")
+            .map(|s| s.to_string());
+        let code = if signal.is_some() {
+            None
+        } else {
+            Some(status.exit_code())
+        };
+
+        Self {
+            code,
+            signal,
+            success: status.success(),
+        }
+    }
+}
+
+/// Outcome of closing a session, returned by `close_session`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionCloseResult {
+    /// True if the child exited on SIGHUP (or had already exited) without escalation
+    pub clean: bool,
+    /// Strongest signal that had to be sent, e.g. "SIGTERM" or "SIGKILL"
+    pub escalated_to: Option<String>,
+    /// Exit status of the reaped child, None if it could not be reaped
+    pub exit_status: Option<SessionExitStatus>,
+}
+
+/// Signals `signal_session` can send, named like `kill -l` ("INT", "TERM", ...)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum SessionSignal {
+    Int,
+    Term,
+    Kill,
+    Stop,
+    Cont,
+    Quit,
 }
 
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+impl SessionSignal {
+    fn number(self) -> i32 {
+        match self {
+            SessionSignal::Int => libc::SIGINT,
+            SessionSignal::Term => libc::SIGTERM,
+            SessionSignal::Kill => libc::SIGKILL,
+            SessionSignal::Stop => libc::SIGSTOP,
+            SessionSignal::Cont => libc::SIGCONT,
+            SessionSignal::Quit => libc::SIGQUIT,
+        }
+    }
+}
+
+/// Interval used when polling the child during the close grace period
+const CLOSE_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Child process handle, shared with `SessionCloser` so closing doesn't hold the session lock
+type SharedChild = Arc<Mutex<Box<dyn portable_pty::Child + Send>>>;
+
 /// Terminal session that manages PTY and passes raw output to frontend
-/// VTE parsing is handled by xterm.js on the frontend
+/// xterm.js renders the output, the backend keeps its own screen model for queries
 pub struct TerminalSession {
-    child: Box<dyn portable_pty::Child + Send>,
-    reader: Arc<Mutex<Box<dyn Read + Send>>>,
+    child: SharedChild,
+    reader: Option<Box<dyn Read + Send>>,
     master_pty: Box<dyn MasterPty + Send>,
     child_pid: u32,
+    /// Recent output, filled by the reader thread
+    scrollback: Arc<Mutex<ScrollbackBuffer>>,
+    /// Parsed screen state, fed by the reader thread
+    screen: Arc<Mutex<TerminalScreen>>,
+    /// Taken by the caller after `start_reader`
+    shell_events: Option<ShellEventReceiver>,
+    /// Output triggers, matched by the reader thread
+    triggers: Arc<Mutex<TriggerMatcher>>,
+    /// Taken by the caller after `start_reader`
+    trigger_events: Option<TriggerEventReceiver>,
+    /// Active asciicast recording, the reader thread appends output to it
+    recording: Arc<Mutex<Option<Recorder>>>,
+    /// Active "log to file" mode, the reader thread appends output to it
+    log: Arc<Mutex<Option<SessionLogger>>>,
+    /// Ack window between the output loop and the frontend
+    output_flow: Arc<FlowControl>,
+    metrics: Arc<SessionMetrics>,
+    input_encoding: InputEncoding,
+    /// Resolved program, the user's shell when none was given
+    shell: String,
+    kind: SessionKind,
+    /// Arguments the caller gave, without the login and integration flags
+    args: Vec<String>,
+    /// Title reported by the frontend, kept so it can be persisted
+    title: Option<String>,
 }
 
 impl TerminalSession {
     /// Create a new terminal session
     /// Returns the session, a separate writer handle, and the child PID
-    pub fn new(cols: usize, rows: usize, shell: String) -> std::io::Result<(Self, PtyWriter, u32)> {
+    pub fn new(shell: String, spec: &SessionSpec) -> std::io::Result<(Self, PtyWriter, u32)> {
+        let input_encoding = match &spec.input_encoding {
+            Some(label) => InputEncoding::for_label(label).ok_or_else(|| unknown_encoding(label))?,
+            None => InputEncoding::default(),
+        };
+        let mut triggers = TriggerMatcher::default();
+        triggers
+            .set_triggers(&spec.triggers)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
         let pty_system = native_pty_system();
         let pty_size = PtySize {
-            rows: rows as u16,
-            cols: cols as u16,
+            rows: spec.rows,
+            cols: spec.cols,
             pixel_width: 0,
             pixel_height: 0,
         };
 
-        // Detect if this is a Unix shell that supports login mode
-        let is_unix_shell = shell.ends_with("zsh")
-            || shell.ends_with("bash")
-            || shell.ends_with("sh")
-            || shell == "zsh"
-            || shell == "bash"
-            || shell == "sh";
-
-        let mut cmd = CommandBuilder::new(&shell);
+        let launch = shell_profile::resolve(&shell, spec)?;
+        let mut cmd = CommandBuilder::new(&launch.program);
+        // Login shells (-l) load .zprofile/.bash_profile, which is critical for packaged
+        // apps to get the proper PATH with Homebrew, asdf, nvm, etc.
+        cmd.args(&launch.args);
 
-        // Use login shell (-l) to load .zprofile/.bash_profile
-        // This is critical for packaged apps to get proper PATH with Homebrew, asdf, nvm, etc.
-        // Set explicit environment variables as fallback for edge cases
-        #[cfg(any(target_os = "macos", target_os = "linux"))]
-        if is_unix_shell {
-            cmd.arg("-l");
+        // portable-pty falls back to HOME when this is not a directory
+        if let Some(cwd) = spec.resolved_cwd() {
+            cmd.cwd(cwd);
         }
 
         // Set explicit environment variables for critical session info
@@ -64,7 +230,7 @@ impl TerminalSession {
             cmd.env("LOGNAME", user);
         }
 
-        cmd.env("SHELL", &shell);
+        cmd.env("SHELL", &launch.shell_env);
 
         // Set TERM environment variable to ensure proper terminal behavior
         cmd.env("TERM", "xterm-256color");
@@ -86,6 +252,19 @@ impl TerminalSession {
             let augmented_path = format!("{}:{}", common_paths, existing_path);
             cmd.env("PATH", augmented_path);
         }
+
+        for (key, value) in &launch.env {
+            cmd.env(key, value);
+        }
+
+        // Caller-provided environment goes last so it can override the defaults above
+        for (key, value) in &spec.env {
+            cmd.env(key, value);
+        }
+        for key in &spec.env_remove {
+            cmd.env_remove(key);
+        }
+
         let pty_pair = pty_system
             .openpty(pty_size)
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
@@ -110,13 +289,31 @@ impl TerminalSession {
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
 
         // Create the writer handle separately so it can be used without locking the session
-        let writer_handle: PtyWriter = Arc::new(Mutex::new(writer));
+        let writer_handle = PtyWriter::spawn(writer, format!("pty-writer-{}", child_pid))?;
 
         let session = Self {
-            child,
-            reader: Arc::new(Mutex::new(reader)),
+            child: Arc::new(Mutex::new(child)),
+            reader: Some(reader),
             master_pty: pty_pair.master,
             child_pid,
+            scrollback: Arc::new(Mutex::new(ScrollbackBuffer::new(spec.scrollback_bytes))),
+            screen: Arc::new(Mutex::new(TerminalScreen::new(
+                spec.cols as usize,
+                spec.rows as usize,
+                DEFAULT_SCROLLBACK_LINES,
+            ))),
+            shell_events: None,
+            triggers: Arc::new(Mutex::new(triggers)),
+            trigger_events: None,
+            recording: Arc::new(Mutex::new(None)),
+            log: Arc::new(Mutex::new(None)),
+            output_flow: Arc::new(FlowControl::default()),
+            metrics: Arc::new(SessionMetrics::default()),
+            input_encoding,
+            shell: launch.program,
+            kind: spec.kind,
+            args: spec.args.clone(),
+            title: None,
         };
 
         Ok((session, writer_handle, child_pid))
@@ -134,102 +331,616 @@ impl TerminalSession {
         self.master_pty
             .resize(size)
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+        self.screen.lock().unwrap().resize(cols, rows);
+        self.with_recorder(|recorder| recorder.record_resize(cols as u16, rows as u16));
 
         Ok(())
     }
 
-    /// Read raw output from PTY (non-blocking)
-    /// Returns Some(bytes) if data is available, None otherwise
-    pub fn read_output(&mut self) -> std::io::Result<Option<Vec<u8>>> {
-        let mut buffer = [0u8; 8192];
+    /// Start the dedicated reader thread for this session
+    /// The thread blocks on the PTY and forwards each chunk through the returned channel.
+    /// The channel closes when the PTY reaches EOF or errors, so the receiver sees `None`.
+    pub fn start_reader(&mut self) -> std::io::Result<PtyOutputReceiver> {
+        let mut reader = self.reader.take().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::Other, "Reader already started")
+        })?;
+        let (tx, rx) = mpsc::channel(OUTPUT_CHANNEL_CAPACITY);
+        let scrollback = self.scrollback.clone();
+        let screen = self.screen.clone();
+        let recording = self.recording.clone();
+        let log = self.log.clone();
+        let metrics = self.metrics.clone();
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+        self.shell_events = Some(events_rx);
+        let triggers = self.triggers.clone();
+        let (triggers_tx, triggers_rx) = mpsc::unbounded_channel();
+        self.trigger_events = Some(triggers_rx);
+
+        thread::Builder::new()
+            .name(format!("pty-reader-{}", self.child_pid))
+            .spawn(move || {
+                let mut buffer = [0u8; READ_CHUNK_SIZE];
+                loop {
+                    match reader.read(&mut buffer) {
+                        // EOF - terminal closed
+                        Ok(0) => break,
+                        Ok(n) => {
+                            metrics.record_read(n);
+                            scrollback.lock().unwrap().push(&buffer[..n]);
+                            let events = {
+                                let mut screen = screen.lock().unwrap();
+                                screen.process(&buffer[..n]);
+                                screen.take_shell_events()
+                            };
+                            for event in events {
+                                let _ = events_tx.send(event);
+                            }
+                            for event in triggers.lock().unwrap().process(&buffer[..n]) {
+                                let _ = triggers_tx.send(event);
+                            }
+                            record(&recording, |recorder| recorder.record_output(&buffer[..n]));
+                            write_log(&log, &buffer[..n]);
+                            // Receiver dropped means nobody is listening anymore
+                            if tx.blocking_send(buffer[..n].to_vec()).is_err() {
+                                break;
+                            }
+                        }
+                        Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                        // EIO is what Linux returns once the slave side is gone
+                        Err(_) => break,
+                    }
+                }
+                // Nothing more will be logged, write the last line and close the document
+                if let Some(logger) = log.lock().unwrap().take() {
+                    let path = logger.path().to_path_buf();
+                    if let Err(e) = logger.finish() {
+                        eprintln!("Failed to finish log {}: {}", path.display(), e);
+                    }
+                }
+            })?;
+
+        Ok(rx)
+    }
+
+    /// Copy the recent output kept in the scrollback buffer
+    pub fn snapshot(&self) -> ScrollbackSnapshot {
+        self.scrollback.lock().unwrap().snapshot()
+    }
+
+    /// Copy at most the last `max_bytes` of recent output
+    pub fn snapshot_tail(&self, max_bytes: usize) -> ScrollbackSnapshot {
+        self.scrollback.lock().unwrap().snapshot_tail(max_bytes)
+    }
 
-        // Try to acquire reader lock
-        let reader_result = self.reader.try_lock();
-        if reader_result.is_err() {
-            // Lock is held by another operation, skip this read
-            return Ok(None);
+    /// Prepend output that did not come from this PTY, e.g. history of a restored session
+    /// Must be called before `start_reader` so it stays ahead of the live output.
+    pub fn seed_scrollback(&mut self, data: &[u8]) {
+        self.scrollback.lock().unwrap().push(data);
+        self.screen.lock().unwrap().process_history(data);
+    }
+
+    /// Current screen contents, cursor and modes as parsed from the output
+    pub fn screen_snapshot(&self, include_scrollback: bool) -> ScreenSnapshot {
+        self.screen.lock().unwrap().snapshot(include_scrollback)
+    }
+
+    /// Start writing output, input and resizes to an asciicast v2 file at `path`
+    pub fn start_recording(&self, path: &std::path::Path) -> std::io::Result<()> {
+        let mut recording = self.recording.lock().unwrap();
+        if recording.is_some() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "Session is already being recorded",
+            ));
         }
 
-        let mut reader = reader_result.unwrap();
-        match reader.read(&mut buffer) {
-            Ok(0) => {
-                // EOF - terminal closed
-                Ok(None)
-            }
-            Ok(n) => {
-                // Got data, return raw bytes
-                Ok(Some(buffer[..n].to_vec()))
-            }
-            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                // No data available right now
-                Ok(None)
-            }
-            Err(e) => {
-                // Actual error
-                Err(e)
+        let (cols, rows) = {
+            let screen = self.screen.lock().unwrap();
+            (screen.cols() as u16, screen.rows() as u16)
+        };
+        *recording = Some(Recorder::create(path, cols, rows, self.title(), &self.shell)?);
+        Ok(())
+    }
+
+    /// Stop the active recording, None if the session wasn't being recorded
+    pub fn stop_recording(&self) -> std::io::Result<Option<RecordingInfo>> {
+        let recorder = self.recording.lock().unwrap().take();
+        recorder.map(Recorder::finish).transpose()
+    }
+
+    /// Add input sent to the PTY to the active recording and time how long until it echoes
+    /// Input isn't recorded while echo is off, so passwords typed at a prompt stay out of
+    /// the cast. Full-screen programs turn echo off too, their output still shows the effect.
+    pub fn record_input(&self, data: &[u8]) {
+        self.metrics.record_input();
+        if self.echo_disabled() {
+            return;
+        }
+        self.with_recorder(|recorder| recorder.record_input(data));
+    }
+
+    /// The program turned off echo in the PTY, e.g. while reading a password
+    fn echo_disabled(&self) -> bool {
+        #[cfg(any(target_os = "macos", target_os = "linux"))]
+        if let Some(fd) = self.master_pty.as_raw_fd() {
+            let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+            if unsafe { libc::tcgetattr(fd, &mut termios) } == 0 {
+                return termios.c_lflag & libc::ECHO == 0;
             }
         }
+        false
+    }
+
+    fn with_recorder(&self, f: impl FnOnce(&mut Recorder) -> std::io::Result<()>) {
+        record(&self.recording, f);
+    }
+
+    /// Start writing output to a log file, converted according to `options`
+    pub fn start_log(&self, path: &std::path::Path, options: LogOptions) -> std::io::Result<()> {
+        let mut log = self.log.lock().unwrap();
+        if log.is_some() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "Session is already being logged",
+            ));
+        }
+        *log = Some(SessionLogger::create(path, options)?);
+        Ok(())
+    }
+
+    /// Stop logging, returns false if the session wasn't being logged
+    pub fn stop_log(&self) -> std::io::Result<bool> {
+        match self.log.lock().unwrap().take() {
+            Some(logger) => logger.finish().map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Encode text typed into the session in its input encoding
+    pub fn encode_input<'a>(&self, text: &'a str) -> std::borrow::Cow<'a, [u8]> {
+        self.input_encoding.encode(text)
+    }
+
+    /// Name of the encoding used for text input
+    pub fn input_encoding(&self) -> &'static str {
+        self.input_encoding.name()
+    }
+
+    pub fn set_input_encoding(&mut self, label: &str) -> std::io::Result<()> {
+        self.input_encoding = InputEncoding::for_label(label).ok_or_else(|| unknown_encoding(label))?;
+        Ok(())
+    }
+
+    /// Throughput and latency counters, shared with the reader thread
+    pub fn metrics(&self) -> Arc<SessionMetrics> {
+        self.metrics.clone()
+    }
+
+    /// Flow control for the session's output, acked by whoever renders it
+    pub fn output_flow(&self) -> Arc<FlowControl> {
+        self.output_flow.clone()
+    }
+
+    /// Receiver for shell integration events, available once after `start_reader`
+    pub fn take_shell_events(&mut self) -> Option<ShellEventReceiver> {
+        self.shell_events.take()
+    }
+
+    /// Receiver for output triggers that matched, available once after `start_reader`
+    pub fn take_trigger_events(&mut self) -> Option<TriggerEventReceiver> {
+        self.trigger_events.take()
+    }
+
+    /// Replace the output triggers, e.g. after the config file changed
+    pub fn set_triggers(&self, triggers: &[TriggerConfig]) -> Result<(), String> {
+        self.triggers.lock().unwrap().set_triggers(triggers)
+    }
+
+    /// Working directory last reported by the shell through OSC 7
+    pub fn reported_cwd(&self) -> Option<String> {
+        self.screen.lock().unwrap().shell_integration().cwd().map(|cwd| cwd.to_string())
+    }
+
+    /// Shared handle to the screen model for callers that need more than a snapshot
+    pub fn screen(&self) -> Arc<Mutex<TerminalScreen>> {
+        self.screen.clone()
+    }
+
+    /// Shell (or program) the session was spawned with
+    pub fn shell(&self) -> &str {
+        &self.shell
+    }
+
+    /// Whether the session runs a shell or a plain command
+    pub fn kind(&self) -> SessionKind {
+        self.kind
+    }
+
+    /// Arguments from the session's spec, all arguments of a command session
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// PID of the session's child process
+    pub fn child_pid(&self) -> u32 {
+        self.child_pid
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    pub fn set_title(&mut self, title: Option<String>) {
+        self.title = title.filter(|t| !t.is_empty());
     }
 
     /// Check if the terminal is still alive
     pub fn is_alive(&mut self) -> bool {
         // Try to poll the child process
         // Returns true if still running (None result), false if exited
-        self.child.try_wait().map(|status| status.is_none()).unwrap_or(true)
+        self.child.lock().unwrap().try_wait().map(|status| status.is_none()).unwrap_or(true)
+    }
+
+    /// Poll the child process for its exit status
+    /// Returns None while the process is still running
+    pub fn try_wait(&mut self) -> Option<SessionExitStatus> {
+        try_wait_child(&self.child)
+    }
+
+    /// Send a signal to the foreground process group, or to `pid` if it belongs to the session
+    /// Holds the session while scanning processes, shared sessions should use `processes`.
+    pub fn signal(&self, signal: SessionSignal, pid: Option<u32>) -> std::io::Result<u32> {
+        self.processes().signal(signal, pid)
+    }
+
+    /// Terminate the session's processes and reap the child, see `SessionCloser::terminate`
+    /// Holds the session for the whole grace period, shared sessions should use `closer`.
+    pub fn terminate(&mut self, grace_period: Duration) -> SessionCloseResult {
+        self.closer().terminate(grace_period)
+    }
+
+    /// Handle that terminates the session once the session lock has been released
+    /// Output flow control is turned off right away, nobody acks a closed session's output.
+    pub fn closer(&self) -> SessionCloser {
+        self.output_flow.disable();
+        SessionCloser {
+            child: self.child.clone(),
+            child_pid: self.child_pid,
+        }
+    }
+
+    /// PID of the foreground process group leader, the shell itself when no job is running
+    pub fn foreground_pid(&self) -> u32 {
+        #[cfg(any(target_os = "macos", target_os = "linux"))]
+        if let Some(fd) = self.master_pty.as_raw_fd() {
+            let pgrp = unsafe { libc::tcgetpgrp(fd) };
+            if pgrp > 0 {
+                return pgrp as u32;
+            }
+        }
+        self.child_pid
     }
 
-    /// Get process information for the foreground process
+    /// Foreground job of the session, with `is_shell` set when that is the shell itself
     pub fn get_process_info(&self) -> Option<ProcessInfo> {
-        #[cfg(target_os = "macos")]
+        let mut system = PROCESSES.get_or_init(|| Mutex::new(System::new())).lock().unwrap();
+        foreground_process_info(
+            &mut system,
+            self.foreground_pid(),
+            self.shell_pid(),
+            self.reported_cwd(),
+        )
+    }
+
+    /// PID of the session's shell, None for a command session whose child is the program
+    fn shell_pid(&self) -> Option<u32> {
+        (self.kind == SessionKind::Shell).then_some(self.child_pid)
+    }
+
+    /// The shell and everything it started
+    /// Holds the session while scanning processes, shared sessions should use `processes`.
+    pub fn process_tree(&self) -> Option<ProcessNode> {
+        self.processes().tree()
+    }
+
+    /// Handle for the lookups that scan every process, to run once the session lock has
+    /// been released
+    pub fn processes(&self) -> SessionProcesses {
+        SessionProcesses {
+            child_pid: self.child_pid,
+            is_shell: self.kind == SessionKind::Shell,
+            foreground_pid: self.foreground_pid(),
+            reported_cwd: self.reported_cwd(),
+        }
+    }
+}
+
+/// A session's shell and foreground job as of `TerminalSession::processes`
+pub struct SessionProcesses {
+    child_pid: u32,
+    is_shell: bool,
+    foreground_pid: u32,
+    reported_cwd: Option<String>,
+}
+
+impl SessionProcesses {
+    /// The shell, or the command, and everything it started
+    pub fn tree(self) -> Option<ProcessNode> {
+        let mut tree = get_process_tree(self.child_pid)?;
+        tree.info.is_shell = self.is_shell;
+        if let (true, Some(cwd)) = (self.is_shell, self.reported_cwd) {
+            tree.info.cwd = cwd;
+        }
+        Some(tree)
+    }
+
+    /// Send a signal to the foreground process group, or to `pid` if it belongs to the session
+    /// Unlike writing ^C this works when the program has the terminal in raw mode.
+    /// Returns the PID or process group that was signalled.
+    pub fn signal(&self, signal: SessionSignal, pid: Option<u32>) -> std::io::Result<u32> {
+        #[cfg(any(target_os = "macos", target_os = "linux"))]
         {
-            self.get_process_info_unix()
+            let (target, kill_target) = match pid {
+                Some(pid) => {
+                    let in_session = get_process_tree(self.child_pid)
+                        .is_some_and(|tree| tree_contains(&tree, pid));
+                    if !in_session {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::PermissionDenied,
+                            format!("Process {} is not part of this session", pid),
+                        ));
+                    }
+                    (pid, pid as i32)
+                }
+                None => {
+                    let pgrp = self.foreground_pid;
+                    (pgrp, -(pgrp as i32))
+                }
+            };
+            if unsafe { libc::kill(kill_target, signal.number()) } != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(target)
         }
-        #[cfg(target_os = "linux")]
+        #[cfg(target_os = "windows")]
         {
-            self.get_process_info_unix()
+            let _ = (signal, pid, self.foreground_pid);
+            Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "Signals are not supported on Windows",
+            ))
+        }
+    }
+}
+
+/// Terminates a session's processes without access to the session itself
+pub struct SessionCloser {
+    child: SharedChild,
+    child_pid: u32,
+}
+
+impl SessionCloser {
+    /// Terminate the session's processes and reap the child
+    /// Sends SIGHUP to every process group in the PTY's session, then escalates to
+    /// SIGTERM and finally SIGKILL if the child is still alive after `grace_period`.
+    /// Blocks for up to twice the grace period, so call it off the async runtime.
+    pub fn terminate(self, grace_period: Duration) -> SessionCloseResult {
+        if let Some(status) = try_wait_child(&self.child) {
+            return SessionCloseResult {
+                clean: true,
+                escalated_to: None,
+                exit_status: Some(status),
+            };
+        }
+
+        #[cfg(any(target_os = "macos", target_os = "linux"))]
+        {
+            self.terminate_unix(grace_period)
         }
         #[cfg(target_os = "windows")]
         {
-            self.get_process_info_windows()
+            let _ = grace_period;
+            self.terminate_windows()
         }
     }
 
+    /// Groups are looked up again before every signal, so a group whose processes are
+    /// gone can't be signalled after its ID was reused
     #[cfg(any(target_os = "macos", target_os = "linux"))]
-    fn get_process_info_unix(&self) -> Option<ProcessInfo> {
-        // Get the foreground process group ID from the PTY
-        let fd = match self.master_pty.as_raw_fd() {
-            Some(fd) => fd,
-            None => {
-                // If we can't get FD, just use the child PID
-                return self.get_process_by_pid(self.child_pid);
+    fn terminate_unix(self, grace_period: Duration) -> SessionCloseResult {
+        self.signal_groups(libc::SIGHUP);
+        if let Some(status) = self.wait_timeout(grace_period) {
+            // Background jobs that ignored SIGHUP must not outlive the tab
+            self.signal_groups(libc::SIGKILL);
+            return SessionCloseResult {
+                clean: true,
+                escalated_to: None,
+                exit_status: Some(status),
+            };
+        }
+
+        self.signal_groups(libc::SIGTERM);
+        if let Some(status) = self.wait_timeout(grace_period) {
+            self.signal_groups(libc::SIGKILL);
+            return SessionCloseResult {
+                clean: false,
+                escalated_to: Some("SIGTERM".to_string()),
+                exit_status: Some(status),
+            };
+        }
+
+        self.signal_groups(libc::SIGKILL);
+        let exit_status = self.child.lock().unwrap().wait().ok().map(SessionExitStatus::from);
+        SessionCloseResult {
+            clean: false,
+            escalated_to: Some("SIGKILL".to_string()),
+            exit_status,
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    fn terminate_windows(self) -> SessionCloseResult {
+        // No process groups or signals on Windows, kill the child directly
+        let mut child = self.child.lock().unwrap();
+        let _ = child.kill();
+        let exit_status = child.wait().ok().map(SessionExitStatus::from);
+        SessionCloseResult {
+            clean: false,
+            escalated_to: Some("kill".to_string()),
+            exit_status,
+        }
+    }
+
+    /// Poll the child until it exits or the timeout elapses
+    fn wait_timeout(&self, timeout: Duration) -> Option<SessionExitStatus> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(status) = try_wait_child(&self.child) {
+                return Some(status);
             }
-        };
+            if Instant::now() >= deadline {
+                return None;
+            }
+            thread::sleep(CLOSE_POLL_INTERVAL);
+        }
+    }
 
-        let fg_pid = unsafe {
-            let pgrp = libc::tcgetpgrp(fd);
-            if pgrp < 0 {
-                // If we can't get the foreground process group, use the child PID
-                self.child_pid
-            } else {
-                pgrp as u32
+    /// Send a signal to every process group that still has a member in the PTY's session
+    /// The shell is the session leader, so its PID is the session ID. Jobs it started live
+    /// in their own groups within the same session, and stay there after the shell exits.
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    fn signal_groups(&self, signal: i32) {
+        let session_id = self.child_pid as i32;
+        let mut groups = Vec::new();
+        let mut system = System::new();
+        system.refresh_processes(ProcessesToUpdate::All, true);
+        for (pid, process) in system.processes() {
+            if process.session_id().map(|sid| sid.as_u32() as i32) != Some(session_id) {
+                continue;
             }
-        };
+            let pgrp = unsafe { libc::getpgid(pid.as_u32() as i32) };
+            if pgrp > 0 && !groups.contains(&pgrp) {
+                groups.push(pgrp);
+            }
+        }
+        signal_process_groups(&groups, signal);
+    }
+}
 
-        self.get_process_by_pid(fg_pid)
+fn try_wait_child(child: &SharedChild) -> Option<SessionExitStatus> {
+    child.lock().unwrap().try_wait().ok().flatten().map(SessionExitStatus::from)
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn tree_contains(node: &ProcessNode, pid: u32) -> bool {
+    node.info.pid == pid || node.children.iter().any(|child| tree_contains(child, pid))
+}
+
+/// Send a signal to each process group, ignoring groups that no longer exist
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn signal_process_groups(groups: &[i32], signal: i32) {
+    for &pgrp in groups {
+        unsafe {
+            libc::kill(-pgrp, signal);
+        }
     }
+}
+
+fn unknown_encoding(label: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        format!("Unknown input encoding: {}", label),
+    )
+}
 
-    /// Helper function to get process info by PID
-    fn get_process_by_pid(&self, pid: u32) -> Option<ProcessInfo> {
-        get_process_info_by_pid_impl(pid)
+/// Run `f` on the active recorder, a write error ends the recording
+fn record(recording: &Mutex<Option<Recorder>>, f: impl FnOnce(&mut Recorder) -> std::io::Result<()>) {
+    let mut recording = recording.lock().unwrap();
+    if let Some(recorder) = recording.as_mut() {
+        if let Err(e) = f(recorder) {
+            eprintln!("Recording to {} stopped: {}", recorder.path().display(), e);
+            *recording = None;
+        }
     }
+}
 
-    #[cfg(target_os = "windows")]
-    fn get_process_info_windows(&self) -> Option<ProcessInfo> {
-        // For Windows, we'll use the child PID directly
-        get_process_info_by_pid_impl(self.child_pid)
+/// Append output to the active log, a write error ends logging
+fn write_log(log: &Mutex<Option<SessionLogger>>, data: &[u8]) {
+    let mut log = log.lock().unwrap();
+    if let Some(logger) = log.as_mut() {
+        if let Err(e) = logger.write(data) {
+            eprintln!("Logging to {} stopped: {}", logger.path().display(), e);
+            *log = None;
+        }
     }
 }
 
+/// Lock a session, adding the time spent waiting for the lock to its metrics
+pub fn lock_session(session: &Mutex<TerminalSession>) -> MutexGuard<'_, TerminalSession> {
+    let start = Instant::now();
+    let session = session.lock().unwrap();
+    session.metrics.record_lock_wait(start.elapsed());
+    session
+}
+
+/// Send a session's foreground process whenever its name or cwd change
+/// Each check is a `tcgetpgrp` and a lookup of that one PID, done on a thread of its own with its
+/// own process table so neither the runtime nor process tree scans wait on it. Stops once the
+/// session is dropped or the receiver is closed.
+pub fn watch_foreground_process(session: &Arc<Mutex<TerminalSession>>) -> ProcessChangeReceiver {
+    let name = format!("process-watch-{}", lock_session(session).child_pid);
+    let session: Weak<Mutex<TerminalSession>> = Arc::downgrade(session);
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    let spawned = std::thread::Builder::new().name(name).spawn(move || {
+        let mut system = System::new();
+        let mut last: Option<ProcessInfo> = None;
+        loop {
+            std::thread::sleep(PROCESS_WATCH_INTERVAL);
+            let (pid, shell_pid, reported_cwd) = {
+                let Some(session) = session.upgrade() else {
+                    break;
+                };
+                let session = lock_session(&session);
+                (session.foreground_pid(), session.shell_pid(), session.reported_cwd())
+            };
+
+            let info = foreground_process_info(&mut system, pid, shell_pid, reported_cwd);
+            let Some(info) = info else {
+                continue;
+            };
+            if !last.as_ref().is_some_and(|last| last.same_process(&info)) {
+                if tx.send(info.clone()).is_err() {
+                    break;
+                }
+                last = Some(info);
+            }
+        }
+    });
+    if let Err(e) = spawned {
+        eprintln!("Failed to start process watcher: {}", e);
+    }
+
+    rx
+}
+
+/// Look up the foreground process of a session whose shell is `shell_pid`
+/// Command sessions have no shell, their program is never reported as one.
+fn foreground_process_info(
+    system: &mut System,
+    pid: u32,
+    shell_pid: Option<u32>,
+    reported_cwd: Option<String>,
+) -> Option<ProcessInfo> {
+    let mut info = lookup_process(system, pid)?;
+    info.is_shell = shell_pid == Some(pid);
+    // The shell's own cwd is known exactly when shell integration reports it
+    if let (true, Some(cwd)) = (info.is_shell, reported_cwd) {
+        info.cwd = cwd;
+    }
+    Some(info)
+}
+
 /// Get process information by PID (public function that doesn't require session lock)
 pub fn get_process_info_by_pid(pid: u32) -> Option<ProcessInfo> {
     get_process_info_by_pid_impl(pid)
@@ -237,18 +948,87 @@ pub fn get_process_info_by_pid(pid: u32) -> Option<ProcessInfo> {
 
 /// Internal implementation of process info lookup
 fn get_process_info_by_pid_impl(pid: u32) -> Option<ProcessInfo> {
-    // Use sysinfo to get process name
-    let mut system = System::new();
-    system.refresh_processes(ProcessesToUpdate::All, true);
+    let mut system = PROCESSES.get_or_init(|| Mutex::new(System::new())).lock().unwrap();
+    lookup_process(&mut system, pid)
+}
 
+/// Look up `pid` in `system`, refreshing only this PID
+fn lookup_process(system: &mut System, pid: u32) -> Option<ProcessInfo> {
     let process_pid = sysinfo::Pid::from_u32(pid);
-    let process = system.process(process_pid)?;
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[process_pid]),
+        true,
+        process_refresh_kind(),
+    );
+    system.process(process_pid).map(process_info)
+}
+
+/// What lookups refresh: command line, CPU and memory
+fn process_refresh_kind() -> ProcessRefreshKind {
+    ProcessRefreshKind::new()
+        .with_cmd(UpdateKind::Always)
+        .with_cpu()
+        .with_memory()
+}
+
+fn process_info(process: &sysinfo::Process) -> ProcessInfo {
+    let pid = process.pid().as_u32();
     let name = process.name().to_string_lossy().to_string();
+    let mut argv: Vec<String> = process
+        .cmd()
+        .iter()
+        .map(|arg| arg.to_string_lossy().to_string())
+        .collect();
+    if argv.is_empty() {
+        argv.push(name.clone());
+    }
+
+    ProcessInfo {
+        name,
+        // Get cwd using platform-specific method
+        cwd: get_process_cwd(pid).unwrap_or_else(|| "~".to_string()),
+        pid,
+        argv,
+        start_time: process.start_time(),
+        cpu_percent: process.cpu_usage(),
+        rss_bytes: process.memory(),
+        is_shell: false,
+    }
+}
+
+/// Process `root` and all its descendants
+pub fn get_process_tree(root: u32) -> Option<ProcessNode> {
+    let mut system = PROCESSES.get_or_init(|| Mutex::new(System::new())).lock().unwrap();
+    system.refresh_processes_specifics(ProcessesToUpdate::All, true, process_refresh_kind());
 
-    // Get cwd using platform-specific method
-    let cwd = get_process_cwd(pid).unwrap_or_else(|| "~".to_string());
+    let mut children: HashMap<u32, Vec<&sysinfo::Process>> = HashMap::new();
+    for process in system.processes().values() {
+        // Threads show up as processes on Linux, they'd duplicate their process
+        if process.thread_kind().is_some() {
+            continue;
+        }
+        if let Some(parent) = process.parent() {
+            children.entry(parent.as_u32()).or_default().push(process);
+        }
+    }
+
+    fn build(
+        process: &sysinfo::Process,
+        children: &HashMap<u32, Vec<&sysinfo::Process>>,
+    ) -> ProcessNode {
+        let mut nodes: Vec<ProcessNode> = children
+            .get(&process.pid().as_u32())
+            .map(|list| list.iter().map(|child| build(child, children)).collect())
+            .unwrap_or_default();
+        nodes.sort_by_key(|node| node.info.pid);
+        ProcessNode {
+            info: process_info(process),
+            children: nodes,
+        }
+    }
 
-    Some(ProcessInfo { name, cwd })
+    let root = system.process(sysinfo::Pid::from_u32(root))?;
+    Some(build(root, &children))
 }
 
 /// Get the current working directory of a process
@@ -0,0 +1,138 @@
+use std::future::Future;
+use std::io::Write;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+
+/// Data for the writer thread, with where to report the result
+struct WriteJob {
+    data: Vec<u8>,
+    /// Chunk size and pause between chunks, None writes everything at once
+    chunks: Option<(usize, Duration)>,
+    done: oneshot::Sender<std::io::Result<()>>,
+}
+
+/// Handle for writing to a session's PTY
+/// Writes are done one after another on the session's writer thread, in the order they were
+/// made. A throttled paste holds up the input made after it, but never a runtime thread.
+#[derive(Clone)]
+pub struct PtyWriter {
+    jobs: mpsc::Sender<WriteJob>,
+}
+
+impl PtyWriter {
+    /// Start the writer thread, it exits once every handle has been dropped
+    pub fn spawn(writer: Box<dyn Write + Send>, name: String) -> std::io::Result<Self> {
+        let (jobs, jobs_rx) = mpsc::channel();
+        thread::Builder::new()
+            .name(name)
+            .spawn(move || run_writer(writer, jobs_rx))?;
+        Ok(Self { jobs })
+    }
+
+    /// Write `data` and flush it
+    /// The write is queued before this returns, the future only reports its result, so
+    /// writes keep their order even when they are awaited on different tasks.
+    pub fn write(
+        &self,
+        data: Vec<u8>,
+    ) -> impl Future<Output = std::io::Result<()>> + Send + 'static {
+        self.queue(data, None)
+    }
+
+    /// Write `data` in chunks of `chunk_bytes` with `delay` in between, like `write`
+    /// Nothing else is written to the PTY until the last chunk is, so input made meanwhile
+    /// can't land in the middle.
+    pub fn write_chunked(
+        &self,
+        data: Vec<u8>,
+        chunk_bytes: usize,
+        delay: Duration,
+    ) -> impl Future<Output = std::io::Result<()>> + Send + 'static {
+        self.queue(data, Some((chunk_bytes.max(1), delay)))
+    }
+
+    fn queue(
+        &self,
+        data: Vec<u8>,
+        chunks: Option<(usize, Duration)>,
+    ) -> impl Future<Output = std::io::Result<()>> + Send + 'static {
+        let (done, done_rx) = oneshot::channel();
+        let queued = self.jobs.send(WriteJob { data, chunks, done });
+        async move {
+            queued.map_err(|_| writer_closed())?;
+            done_rx.await.unwrap_or_else(|_| Err(writer_closed()))
+        }
+    }
+}
+
+fn run_writer(mut writer: Box<dyn Write + Send>, jobs: mpsc::Receiver<WriteJob>) {
+    for job in jobs {
+        let result = write_job(&mut writer, &job);
+        let _ = job.done.send(result);
+    }
+}
+
+fn write_job(writer: &mut Box<dyn Write + Send>, job: &WriteJob) -> std::io::Result<()> {
+    let Some((chunk_bytes, delay)) = job.chunks else {
+        writer.write_all(&job.data)?;
+        return writer.flush();
+    };
+    for (index, chunk) in job.data.chunks(chunk_bytes).enumerate() {
+        if index > 0 {
+            thread::sleep(delay);
+        }
+        writer.write_all(chunk)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+fn writer_closed() -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::BrokenPipe, "Session writer is closed")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// Collects what is written, standing in for the PTY
+    #[derive(Clone, Default)]
+    struct Sink(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Sink {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn writes_keep_their_order_around_a_chunked_write() {
+        let sink = Sink::default();
+        let writer = PtyWriter::spawn(Box::new(sink.clone()), "test-writer".to_string()).unwrap();
+
+        let paste = writer.write_chunked(b"abcdef".to_vec(), 2, Duration::from_millis(20));
+        // Queued after the paste, awaited before it
+        writer.write(b"!".to_vec()).await.unwrap();
+        paste.await.unwrap();
+        assert_eq!(*sink.0.lock().unwrap(), b"abcdef!");
+    }
+
+    #[tokio::test]
+    async fn writing_after_the_thread_stopped_fails() {
+        let (jobs, jobs_rx) = mpsc::channel();
+        drop(jobs_rx);
+        let writer = PtyWriter { jobs };
+        let error = writer.write(b"x".to_vec()).await.unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::BrokenPipe);
+    }
+}
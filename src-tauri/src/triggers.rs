@@ -0,0 +1,130 @@
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_notification::NotificationExt;
+use tip_term::terminal::daemon::DaemonRequest;
+use tip_term::{lock_session, TriggerAction, TriggerConfig, TriggerEvent, TriggerEventReceiver};
+
+use crate::config::ConfigWatchState;
+use crate::TerminalState;
+
+/// Match to highlight, sent as `terminal-trigger-highlight`
+/// The frontend finds the line by its text near the end of the buffer, output line
+/// numbers don't map to rows once lines wrap or the screen is redrawn.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TriggerHighlight<'a> {
+    session_id: &'a str,
+    line_text: String,
+    start: usize,
+    end: usize,
+    text: String,
+    color: Option<String>,
+}
+
+/// Tab to mark, sent as `terminal-tab-marked`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TabMark<'a> {
+    session_id: &'a str,
+    text: String,
+}
+
+/// Triggers from the terminal config, given to new sessions
+pub fn configured_triggers(app: &AppHandle) -> Vec<TriggerConfig> {
+    let state = app.state::<Arc<Mutex<ConfigWatchState>>>();
+    let state = state.lock().unwrap();
+    state.backend.triggers.clone()
+}
+
+/// Give every running session the triggers from the terminal config
+pub fn apply_triggers(app: &AppHandle) {
+    let triggers = configured_triggers(app);
+    let (local, remote) = {
+        let state = app.state::<Arc<Mutex<TerminalState>>>();
+        let state = state.lock().unwrap();
+        let local: Vec<_> = state.sessions.values().cloned().collect();
+        let remote = state
+            .daemon
+            .clone()
+            .map(|client| (client, state.daemon_sessions.iter().cloned().collect::<Vec<_>>()));
+        (local, remote)
+    };
+
+    for session in local {
+        if let Err(e) = lock_session(&session).set_triggers(&triggers) {
+            eprintln!("Failed to set triggers: {}", e);
+        }
+    }
+    if let Some((client, ids)) = remote {
+        tauri::async_runtime::spawn(async move {
+            for id in ids {
+                let request = DaemonRequest::SetTriggers {
+                    id,
+                    triggers: triggers.clone(),
+                };
+                if let Err(e) = client.request(request).await {
+                    eprintln!("Failed to set triggers: {}", e);
+                }
+            }
+        });
+    }
+}
+
+/// Carry out the action of a trigger that matched a session's output
+pub(crate) fn handle_trigger_event(session_id: &str, event: TriggerEvent, app: &AppHandle) {
+    let result = match event.action {
+        TriggerAction::Highlight { color } => app.emit(
+            "terminal-trigger-highlight",
+            TriggerHighlight {
+                session_id,
+                line_text: event.line_text,
+                start: event.start,
+                end: event.end,
+                text: event.matched,
+                color,
+            },
+        ),
+        TriggerAction::Notify { title, body } => {
+            let title = title.unwrap_or_else(|| "Trigger matched".to_string());
+            let body = body.unwrap_or(event.line_text);
+            if let Err(e) = app.notification().builder().title(title).body(body).show() {
+                eprintln!("Failed to show notification: {}", e);
+            }
+            Ok(())
+        }
+        TriggerAction::Respond { text } => {
+            let app = app.clone();
+            let id = session_id.to_string();
+            tauri::async_runtime::spawn(async move {
+                let state = app.state::<Arc<Mutex<TerminalState>>>();
+                if let Err(e) = crate::write_text(&state, id, text).await {
+                    eprintln!("Failed to write trigger response: {}", e);
+                }
+            });
+            Ok(())
+        }
+        TriggerAction::MarkTab => app.emit(
+            "terminal-tab-marked",
+            TabMark {
+                session_id,
+                text: event.matched,
+            },
+        ),
+    };
+    if let Err(e) = result {
+        eprintln!("Failed to emit trigger event: {}", e);
+    }
+}
+
+/// Handle a local session's trigger events until its reader thread stops
+pub(crate) async fn forward_trigger_events(
+    session_id: String,
+    mut events: TriggerEventReceiver,
+    app: AppHandle,
+) {
+    while let Some(event) = events.recv().await {
+        handle_trigger_event(&session_id, event, &app);
+    }
+}
@@ -0,0 +1,210 @@
+//! Output flow control through the app's output path
+//!
+//! Runs `yes` in a session and forwards its output with `forward_output` and the frame loop,
+//! as the app does, into a channel standing in for the webview. While the frontend doesn't
+//! ack, the output queued in the multiplexer and the unacked output must stay bounded and the
+//! PTY must stop being read; once acks come in, output must flow again. Once the session
+//! exits, its output must be drained whether or not the frontend acks or receives it.
+
+#![cfg(unix)]
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tauri::ipc::{Channel, InvokeResponseBody};
+use tauri::test::MockRuntime;
+use tauri::{App, Manager};
+use tip_term::output::{self, OutputMultiplexer, QUEUE_MAX_BYTES};
+use tip_term::terminal::flow_control::OUTPUT_WINDOW;
+use tip_term::{FlowControl, PtyOutputReceiver, SessionSpec, TerminalSession};
+
+const SESSION_ID: &str = "flow";
+
+/// Size of one PTY read, see `READ_CHUNK_SIZE`
+const READ_CHUNK_BYTES: u64 = 8192;
+
+/// Chunks the reader thread may queue, see `OUTPUT_CHANNEL_CAPACITY`
+const CHANNEL_BYTES: u64 = 64 * READ_CHUNK_BYTES;
+
+/// What the kernel PTY buffer and the chunk in the reader's hands can add
+const PTY_SLACK: u64 = 256 * 1024;
+
+const STALL_DURATION: Duration = Duration::from_secs(2);
+const DRAIN_DURATION: Duration = Duration::from_secs(2);
+
+/// Bytes of `session_id` in an output frame, see `encode_frame`
+fn frame_bytes(mut frame: &[u8], session_id: &str) -> u64 {
+    let mut bytes = 0;
+    while let Some((&id_len, rest)) = frame.split_first() {
+        let (id, rest) = rest.split_at(id_len as usize);
+        let (len, rest) = rest.split_at(4);
+        let len = u32::from_le_bytes(len.try_into().unwrap()) as usize;
+        if id == session_id.as_bytes() {
+            bytes += len as u64;
+        }
+        frame = &rest[len..];
+    }
+    bytes
+}
+
+/// The webview: counts delivered output and acks it right away while `acking` is set
+fn frontend(flow: Arc<FlowControl>, delivered: Arc<AtomicU64>, acking: Arc<AtomicBool>) -> Channel {
+    Channel::new(move |body| {
+        let InvokeResponseBody::Raw(frame) = body else {
+            panic!("Output frames are sent as raw bytes");
+        };
+        let bytes = frame_bytes(&frame, SESSION_ID);
+        delivered.fetch_add(bytes, Ordering::SeqCst);
+        if acking.load(Ordering::SeqCst) {
+            flow.ack(bytes);
+        }
+        Ok(())
+    })
+}
+
+/// A mock app running the frame loop
+fn mock_app() -> (App<MockRuntime>, Arc<OutputMultiplexer>) {
+    let app = tauri::test::mock_app();
+    let mux = Arc::new(OutputMultiplexer::default());
+    app.manage(mux.clone());
+    tokio::spawn(output::run_output_frames(mux.clone(), app.handle().clone()));
+    (app, mux)
+}
+
+/// A session running `command` with its reader started
+fn spawn_session(command: &str) -> (TerminalSession, PtyOutputReceiver) {
+    let spec = SessionSpec {
+        args: vec!["-c".to_string(), command.to_string()],
+        login: false,
+        shell_integration: false,
+        ..SessionSpec::default()
+    };
+    let (mut session, _writer, _pid) =
+        TerminalSession::new("/bin/sh".to_string(), &spec).expect("Failed to spawn session");
+    let output_rx = session.start_reader().expect("Failed to start reader");
+    (session, output_rx)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn output_stays_within_the_window_without_acks() {
+    let (app, mux) = mock_app();
+    let (mut session, mut output_rx) = spawn_session("yes");
+    let flow = session.output_flow();
+
+    let delivered = Arc::new(AtomicU64::new(0));
+    let acking = Arc::new(AtomicBool::new(false));
+    mux.subscribe(frontend(flow.clone(), delivered.clone(), acking.clone()));
+
+    let forwarding = {
+        let flow = flow.clone();
+        let app = app.handle().clone();
+        tokio::spawn(async move {
+            output::forward_output(SESSION_ID, &mut output_rx, Some(flow), None, &app).await;
+        })
+    };
+
+    // A frame can take a whole queue while the window is still open
+    let max_unacked = OUTPUT_WINDOW + QUEUE_MAX_BYTES as u64 + READ_CHUNK_BYTES;
+    let max_queued = QUEUE_MAX_BYTES + READ_CHUNK_BYTES as usize;
+    let max_read = max_unacked + CHANNEL_BYTES + PTY_SLACK;
+
+    tokio::time::sleep(STALL_DURATION).await;
+    let read_while_stalled = session.snapshot().total_bytes;
+    let queued = mux.queued_bytes(SESSION_ID);
+    let unacked = flow.unacked();
+    assert!(
+        queued <= max_queued,
+        "{} bytes queued, bound is {}",
+        queued,
+        max_queued
+    );
+    assert!(
+        unacked <= max_unacked,
+        "{} bytes unacked, bound is {}",
+        unacked,
+        max_unacked
+    );
+    assert_eq!(unacked, delivered.load(Ordering::SeqCst));
+    assert!(
+        read_while_stalled <= max_read,
+        "Read {} bytes without acks, bound is {}",
+        read_while_stalled,
+        max_read
+    );
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(
+        session.snapshot().total_bytes,
+        read_while_stalled,
+        "The PTY kept being read while the window was closed"
+    );
+
+    // What the frontend does after a reload, then it acks everything it renders
+    acking.store(true, Ordering::SeqCst);
+    flow.reset();
+    let delivered_before = delivered.load(Ordering::SeqCst);
+    tokio::time::sleep(DRAIN_DURATION).await;
+    let read_after_acks = session.snapshot().total_bytes;
+    assert!(
+        read_after_acks > read_while_stalled + max_read,
+        "Output didn't resume once acks came in"
+    );
+    assert!(delivered.load(Ordering::SeqCst) > delivered_before + max_unacked);
+    assert!(flow.unacked() <= max_unacked);
+    assert!(mux.queued_bytes(SESSION_ID) <= max_queued);
+
+    session.terminate(Duration::from_millis(200));
+    tokio::time::timeout(Duration::from_secs(10), forwarding)
+        .await
+        .expect("Output wasn't drained after the session ended")
+        .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn exited_session_is_drained_without_acks() {
+    let (app, mux) = mock_app();
+    // More than the window, but little enough to fit in the queue so the PTY reaches EOF
+    let (session, mut output_rx) = spawn_session("head -c 1200000 /dev/zero | tr '\\0' y");
+    let flow = session.output_flow();
+    let delivered = Arc::new(AtomicU64::new(0));
+    mux.subscribe(frontend(
+        flow.clone(),
+        delivered.clone(),
+        Arc::new(AtomicBool::new(false)),
+    ));
+
+    let forwarding =
+        output::forward_output(SESSION_ID, &mut output_rx, Some(flow), None, app.handle());
+    tokio::time::timeout(Duration::from_secs(10), forwarding)
+        .await
+        .expect("Output wasn't drained after the session exited");
+    assert_eq!(
+        delivered.load(Ordering::SeqCst),
+        session.snapshot().total_bytes
+    );
+    assert!(session.snapshot().total_bytes >= 1_200_000);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn output_that_fails_to_send_isnt_counted() {
+    let (app, mux) = mock_app();
+    let (session, mut output_rx) = spawn_session("echo hello");
+    let flow = session.output_flow();
+    mux.subscribe(Channel::new(|_| {
+        Err(tauri::Error::Io(std::io::Error::other("Webview is gone")))
+    }));
+
+    let forwarding = output::forward_output(
+        SESSION_ID,
+        &mut output_rx,
+        Some(flow.clone()),
+        None,
+        app.handle(),
+    );
+    tokio::time::timeout(Duration::from_secs(10), forwarding)
+        .await
+        .expect("Output wasn't drained after the session exited");
+    assert!(session.snapshot().total_bytes > 0);
+    assert_eq!(flow.unacked(), 0);
+}
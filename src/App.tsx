@@ -8,6 +8,7 @@ import TerminalContainer from "./components/terminal/TerminalContainer";
 import QuickOpenModal from "./components/quickopen/QuickOpenModal";
 import { useHotkeyHandler } from "./hooks/useHotkeyHandler";
 import { useProcessPolling } from "./hooks/useProcessPolling";
+import { useTriggerEvents } from "./hooks/useTriggerEvents";
 import { getThemeService } from "./services/themeService";
 import { updateTerminalThemes, updateTerminalCursorSettings } from "./terminal-core/api/terminalApi";
 import { ensureTerminalConfigFile, reloadTerminalConfig } from "./terminal-core/config/loader";
@@ -32,7 +33,13 @@ function App() {
       await reloadTerminalConfig();
       stopWatcher = await startTerminalConfigWatcher();
 
-      if (!cancelled) {
+      if (cancelled) return;
+      // Sessions saved on the last quit come back, otherwise start with a fresh one
+      const restored = await useSessionStore.getState().restoreSessions().catch((error) => {
+        console.error("Failed to restore sessions:", error);
+        return 0;
+      });
+      if (!cancelled && restored === 0) {
         useSessionStore.getState().createSession().catch(console.error);
       }
     };
@@ -100,6 +107,7 @@ function App() {
   }, []);
 
   useProcessPolling();
+  useTriggerEvents();
 
   // Global keyboard shortcuts (handled by useHotkeyHandler hook)
   useHotkeyHandler();
@@ -8,6 +8,7 @@ import { useEditorStore } from "../../stores/editorStore";
 import { useSettingsStore } from "../../stores/settingsStore";
 import { useSidebarStore } from "../../stores/sidebarStore";
 import { useSplitPaneStore } from "../../stores/splitPaneStore";
+import { useTerminalConfigStore } from "../../stores/terminalConfigStore";
 import { invoke } from "@tauri-apps/api/core";
 import { ResultItem } from "./ResultItem";
 import { HotkeyResultItem } from "./HotkeyResultItem";
@@ -239,7 +240,7 @@ export default function QuickOpenModal() {
       splitVertical: () => {
         if (!sessionStore.activeSessionId || sessionStore.isSettingsSession(sessionStore.activeSessionId)) return;
         const rootSessionId = sessionStore.activeSessionId;
-        invoke<string>("create_session", { shell: "/bin/zsh" })
+        invoke<string>("create_session", { shell: useTerminalConfigStore.getState().config.shell })
           .then((newSessionId) => {
             const layout = splitPaneStore.getLayout(rootSessionId);
             if (!layout) {
@@ -257,7 +258,7 @@ export default function QuickOpenModal() {
       splitHorizontal: () => {
         if (!sessionStore.activeSessionId || sessionStore.isSettingsSession(sessionStore.activeSessionId)) return;
         const rootSessionId = sessionStore.activeSessionId;
-        invoke<string>("create_session", { shell: "/bin/zsh" })
+        invoke<string>("create_session", { shell: useTerminalConfigStore.getState().config.shell })
           .then((newSessionId) => {
             const layout = splitPaneStore.getLayout(rootSessionId);
             if (!layout) {
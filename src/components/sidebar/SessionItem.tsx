@@ -203,6 +203,13 @@ export default function SessionItem({ session, index, inGroup: _inGroup = false,
           {(session.notifyWhenDone || session.notifyOnActivity) && (
             <div className="absolute -top-0.5 -right-0.5 w-2 h-2 bg-accent-primary rounded-full" />
           )}
+          {/* Marked by a trigger */}
+          {session.markedBy !== undefined && (
+            <div
+              className="absolute -bottom-0.5 -right-0.5 w-2 h-2 bg-accent-orange rounded-full"
+              title={`Matched: ${session.markedBy}`}
+            />
+          )}
         </div>
         {isEditing ? (
           <input
@@ -6,6 +6,7 @@ import { useEditorStore } from "../stores/editorStore";
 import { useQuickOpenStore } from "../stores/quickOpenStore";
 import { useSettingsStore } from "../stores/settingsStore";
 import { useSplitPaneStore } from "../stores/splitPaneStore";
+import { useTerminalConfigStore } from "../stores/terminalConfigStore";
 import { useGitStore } from "../stores/gitStore";
 import { useTerminalSearchStore } from "../stores/terminalSearchStore";
 import { useFileTreeStore } from "../stores/fileTreeStore";
@@ -226,7 +227,7 @@ export function useHotkeyHandler() {
           const rootSessionId = sessionStore.activeSessionId;
 
           // Create a new PTY session for the new pane
-          invoke<string>("create_session", { shell: "/bin/zsh" })
+          invoke<string>("create_session", { shell: useTerminalConfigStore.getState().config.shell })
             .then((newSessionId) => {
               const layout = splitPaneStore.getLayout(rootSessionId);
 
@@ -262,7 +263,7 @@ export function useHotkeyHandler() {
           const rootSessionId = sessionStore.activeSessionId;
 
           // Create a new PTY session for the new pane
-          invoke<string>("create_session", { shell: "/bin/zsh" })
+          invoke<string>("create_session", { shell: useTerminalConfigStore.getState().config.shell })
             .then((newSessionId) => {
               const layout = splitPaneStore.getLayout(rootSessionId);
 
@@ -1,59 +1,81 @@
 import { useEffect, useRef } from "react";
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 import { useSessionStore } from "../stores/sessionStore";
-import { isShellProcess, sendNotification } from "../utils/notifications";
+import { sendNotification } from "../utils/notifications";
 
 interface ProcessInfo {
   name: string;
   cwd: string;
+  pid: number;
+  argv: string[];
+  startTime: number;
+  cpuPercent: number;
+  rssBytes: number;
+  // True when the foreground process is the session's shell, i.e. no job is running
+  isShell: boolean;
 }
 
+interface SessionProcessChanged extends ProcessInfo {
+  sessionId: string;
+}
+
+// The backend pushes `session-process-changed` whenever a session's foreground
+// process or cwd changes; sessions are only queried once, on mount.
 export function useProcessPolling() {
-  const previousProcesses = useRef<Map<string, string>>(new Map());
+  const previousProcesses = useRef<Map<string, ProcessInfo>>(new Map());
 
   useEffect(() => {
-    const pollInterval = setInterval(async () => {
+    const handleProcessInfo = async (sessionId: string, info: ProcessInfo) => {
       const { getTerminalSessions, updateSessionProcessInfo, activeSessionId } =
         useSessionStore.getState();
-      const sessions = getTerminalSessions();
-
-      for (const session of sessions) {
-        try {
-          const info = await invoke<ProcessInfo | null>("get_session_info", { id: session.id });
-          if (info) {
-            const previousProcess = previousProcesses.current.get(session.id);
-            const currentProcess = info.name;
-
-            if (
-              session.notifyWhenDone &&
-              session.id !== activeSessionId &&
-              previousProcess &&
-              !isShellProcess(previousProcess) &&
-              isShellProcess(currentProcess)
-            ) {
-              await sendNotification({
-                title: "Command Completed",
-                body: `"${previousProcess}" finished`,
-                sessionId: session.id,
-              });
-            }
-
-            previousProcesses.current.set(session.id, currentProcess);
-            updateSessionProcessInfo(session.id, info.name, info.cwd);
-          }
-        } catch (error) {
-          console.error(`[App] Failed to get process info for ${session.id}:`, error);
-        }
+      const session = getTerminalSessions().find((s) => s.id === sessionId);
+      if (!session) {
+        previousProcesses.current.delete(sessionId);
+        return;
       }
 
-      const sessionIds = new Set(sessions.map((s) => s.id));
-      for (const id of previousProcesses.current.keys()) {
-        if (!sessionIds.has(id)) {
-          previousProcesses.current.delete(id);
-        }
+      const previousProcess = previousProcesses.current.get(sessionId);
+
+      if (
+        session.notifyWhenDone &&
+        session.id !== activeSessionId &&
+        previousProcess &&
+        !previousProcess.isShell &&
+        info.isShell
+      ) {
+        await sendNotification({
+          title: "Command Completed",
+          body: `"${previousProcess.name}" finished`,
+          sessionId: session.id,
+        });
       }
-    }, 1500);
 
-    return () => clearInterval(pollInterval);
+      previousProcesses.current.set(sessionId, info);
+      updateSessionProcessInfo(sessionId, info.name, info.cwd);
+    };
+
+    const unlisten = listen<SessionProcessChanged>("session-process-changed", (event) => {
+      const { sessionId, ...info } = event.payload;
+      handleProcessInfo(sessionId, info).catch((error) => {
+        console.error(`[App] Failed to handle process change for ${sessionId}:`, error);
+      });
+    });
+
+    for (const session of useSessionStore.getState().getTerminalSessions()) {
+      invoke<ProcessInfo | null>("get_session_info", { id: session.id })
+        .then((info) => {
+          if (info && !previousProcesses.current.has(session.id)) {
+            return handleProcessInfo(session.id, info);
+          }
+        })
+        .catch((error) => {
+          console.error(`[App] Failed to get process info for ${session.id}:`, error);
+        });
+    }
+
+    return () => {
+      unlisten.then((fn) => fn());
+    };
   }, []);
 }
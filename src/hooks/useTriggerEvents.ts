@@ -0,0 +1,35 @@
+import { useEffect } from "react";
+import { listen } from "@tauri-apps/api/event";
+import { useSessionStore } from "../stores/sessionStore";
+import { highlightTriggerMatch, type TriggerMatch } from "../terminal-core/api/terminalApi";
+
+interface TriggerHighlight extends TriggerMatch {
+  sessionId: string;
+  text: string;
+}
+
+interface TabMark {
+  sessionId: string;
+  text: string;
+}
+
+// Carries out the trigger actions the backend leaves to the frontend: highlighting
+// the match in the terminal and marking the session's tab.
+export function useTriggerEvents() {
+  useEffect(() => {
+    const unlistenHighlight = listen<TriggerHighlight>("terminal-trigger-highlight", (event) => {
+      const { sessionId, ...match } = event.payload;
+      highlightTriggerMatch(sessionId, match);
+    });
+
+    const unlistenMark = listen<TabMark>("terminal-tab-marked", (event) => {
+      const { sessionId, text } = event.payload;
+      useSessionStore.getState().markSession(sessionId, text);
+    });
+
+    return () => {
+      unlistenHighlight.then((fn) => fn());
+      unlistenMark.then((fn) => fn());
+    };
+  }, []);
+}
@@ -1,11 +1,14 @@
 import { create } from "zustand";
 import { invoke } from "@tauri-apps/api/core";
 import { useTerminalConfigStore } from "./terminalConfigStore";
+import { writeTerminalHistory } from "../terminal-core/api/terminalApi";
 import {
   GroupColor,
   GROUP_COLORS,
   GroupInfo,
+  RestoredSession,
   SessionInfo,
+  SessionManifest,
   SidebarItem,
   WorkspaceInfo,
 } from "../types/session";
@@ -24,6 +27,7 @@ interface SessionStore {
 
   // Session Actions
   createSession: (workspaceId?: string, groupId?: string) => Promise<string>;
+  restoreSessions: () => Promise<number>;
   closeSession: (id: string) => Promise<void>;
   setActiveSession: (id: string) => void;
   renameSession: (id: string, name: string) => void;
@@ -32,6 +36,7 @@ interface SessionStore {
   setSessionCustomName: (id: string, customName: string | null) => void;
   setNotifyWhenDone: (id: string, enabled: boolean) => void;
   setNotifyOnActivity: (id: string, enabled: boolean) => void;
+  markSession: (id: string, text: string) => void;
   getSessionsList: () => SessionInfo[];
   reorderSessions: (activeId: string, overId: string) => void;
 
@@ -73,7 +78,7 @@ export const useSessionStore = create<SessionStore>((set, get) => ({
 
   createSession: async (workspaceId?: string, groupId?: string) => {
     try {
-      const shell = useTerminalConfigStore.getState().config.shell || "/bin/zsh";
+      const shell = useTerminalConfigStore.getState().config.shell;
       const id = await invoke<string>("create_session", { shell });
       sessionCounter++;
       const session: SessionInfo = {
@@ -102,6 +107,45 @@ export const useSessionStore = create<SessionStore>((set, get) => ({
     }
   },
 
+  // Recreate the sessions open when the app last quit, returns how many were restored
+  restoreSessions: async () => {
+    const manifest = await invoke<SessionManifest | null>("get_restorable_sessions");
+    if (!manifest) return 0;
+
+    let restored = 0;
+    for (const saved of manifest.sessions) {
+      try {
+        const { id, history } = await invoke<RestoredSession>("restore_session", { id: saved.id });
+        writeTerminalHistory(id, new Uint8Array(history));
+        sessionCounter++;
+        const session: SessionInfo = {
+          id,
+          name: `Session ${sessionCounter}`,
+          type: "terminal",
+          workspaceId: null,
+          groupId: null,
+          createdAt: Date.now(),
+          order: sessionCounter,
+          cwd: saved.cwd ?? undefined,
+          terminalTitle: saved.title ?? undefined,
+        };
+
+        set((state) => {
+          const newSessions = new Map(state.sessions);
+          newSessions.set(id, session);
+          return {
+            sessions: newSessions,
+            activeSessionId: id,
+          };
+        });
+        restored++;
+      } catch (error) {
+        console.error("Failed to restore session:", error);
+      }
+    }
+    return restored;
+  },
+
   closeSession: async (id: string) => {
     try {
       await invoke("close_session", { id });
@@ -142,9 +186,15 @@ export const useSessionStore = create<SessionStore>((set, get) => ({
 
   setActiveSession: (id: string) => {
     const state = get();
-    if (state.sessions.has(id)) {
+    const session = state.sessions.get(id);
+    if (!session) return;
+    if (session.markedBy === undefined) {
       set({ activeSessionId: id });
+      return;
     }
+    const newSessions = new Map(state.sessions);
+    newSessions.set(id, { ...session, markedBy: undefined });
+    set({ activeSessionId: id, sessions: newSessions });
   },
 
   renameSession: (id: string, name: string) => {
@@ -176,6 +226,9 @@ export const useSessionStore = create<SessionStore>((set, get) => ({
 
       const newSessions = new Map(state.sessions);
       newSessions.set(id, { ...session, terminalTitle: title || undefined });
+      if (!session.customName) {
+        invoke("set_session_title", { id, title: title || null }).catch(console.error);
+      }
       return { sessions: newSessions };
     });
   },
@@ -190,6 +243,10 @@ export const useSessionStore = create<SessionStore>((set, get) => ({
         ...session,
         customName: customName || undefined
       });
+      invoke("set_session_title", {
+        id,
+        title: customName || session.terminalTitle || null,
+      }).catch(console.error);
       return { sessions: newSessions };
     });
   },
@@ -222,6 +279,18 @@ export const useSessionStore = create<SessionStore>((set, get) => ({
     });
   },
 
+  // The active tab is already in view, only background tabs get marked
+  markSession: (id: string, text: string) => {
+    set((state) => {
+      const session = state.sessions.get(id);
+      if (!session || state.activeSessionId === id) return state;
+
+      const newSessions = new Map(state.sessions);
+      newSessions.set(id, { ...session, markedBy: text });
+      return { sessions: newSessions };
+    });
+  },
+
   getSessionsList: () => {
     const state = get();
     return Array.from(state.sessions.values()).sort((a, b) => a.order - b.order);
@@ -563,3 +632,10 @@ export const useSessionStore = create<SessionStore>((set, get) => ({
       .sort((a, b) => a.order - b.order);
   },
 }));
+
+// The backend skips command notifications for the session the user is looking at
+useSessionStore.subscribe((state, prev) => {
+  if (state.activeSessionId !== prev.activeSessionId) {
+    invoke("set_active_session", { id: state.activeSessionId }).catch(() => {});
+  }
+});
@@ -13,6 +13,8 @@ export {
   clearSearch,
   serializeTerminal,
   restoreTerminal,
+  writeTerminalHistory,
+  highlightTriggerMatch,
 } from "../terminalRegistry";
 
-export type { TerminalEntry, SearchOptions } from "../terminalRegistry";
+export type { TerminalEntry, SearchOptions, TriggerMatch } from "../terminalRegistry";
@@ -74,8 +74,14 @@ export function buildDefaultTerminalConfig(): TerminalConfig {
     },
     macOptionSelectionMode: "selection",
 
-    shell: "/bin/zsh",
+    shell: "",
     shellArgs: [],
     workingDirectory: null,
+
+    commandNotifications: {
+      enabled: true,
+      minDurationMs: 10000,
+    },
+    triggers: [],
   };
 }
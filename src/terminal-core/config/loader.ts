@@ -103,7 +103,7 @@ function validateTerminalConfig(config: TerminalConfig): { config: TerminalConfi
     errors.push("modifierKeys.cmdIsMeta: invalid");
   }
 
-  if (typeof next.shell !== "string" || next.shell.trim().length === 0) {
+  if (typeof next.shell !== "string") {
     next.shell = defaults.shell;
     errors.push("shell: invalid");
   }
@@ -112,6 +112,42 @@ function validateTerminalConfig(config: TerminalConfig): { config: TerminalConfi
     errors.push("shellArgs: invalid");
   }
 
+  if (typeof next.commandNotifications?.enabled !== "boolean") {
+    next.commandNotifications = {
+      ...next.commandNotifications,
+      enabled: defaults.commandNotifications.enabled,
+    };
+    errors.push("commandNotifications.enabled: invalid");
+  }
+  if (
+    typeof next.commandNotifications?.minDurationMs !== "number" ||
+    next.commandNotifications.minDurationMs < 0
+  ) {
+    next.commandNotifications = {
+      ...next.commandNotifications,
+      minDurationMs: defaults.commandNotifications.minDurationMs,
+    };
+    errors.push("commandNotifications.minDurationMs: must be >= 0");
+  }
+
+  const triggerActions = ["highlight", "notify", "respond", "markTab"] as const;
+  if (!Array.isArray(next.triggers)) {
+    next.triggers = defaults.triggers;
+    errors.push("triggers: must be an array");
+  } else {
+    const valid = next.triggers.filter(
+      (trigger) =>
+        isPlainObject(trigger) &&
+        typeof trigger.pattern === "string" &&
+        isPlainObject(trigger.action) &&
+        isValidEnum(trigger.action.type, triggerActions),
+    );
+    if (valid.length !== next.triggers.length) {
+      errors.push("triggers: entries need a pattern and a known action type");
+    }
+    next.triggers = valid;
+  }
+
   return { config: next, errors };
 }
 
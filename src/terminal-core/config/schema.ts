@@ -8,6 +8,24 @@ export interface TerminalModifierKeys {
   cmdIsMeta: boolean;
 }
 
+export interface CommandNotificationConfig {
+  enabled: boolean;
+  minDurationMs: number;
+}
+
+export type TriggerAction =
+  | { type: "highlight"; color?: string | null }
+  | { type: "notify"; title?: string | null; body?: string | null }
+  | { type: "respond"; text: string }
+  | { type: "markTab" };
+
+export interface TriggerConfig {
+  pattern: string;
+  action: TriggerAction;
+  instant?: boolean;
+  enabled?: boolean;
+}
+
 export interface TerminalConfig {
   fontFamily: string;
   fontSize: number;
@@ -43,7 +61,13 @@ export interface TerminalConfig {
   modifierKeys: TerminalModifierKeys;
   macOptionSelectionMode: MacOptionSelectionMode;
 
+  // Empty for the user's login shell, read by the backend from $SHELL or passwd
   shell: string;
   shellArgs: string[];
   workingDirectory?: string | null;
+
+  // Read by the backend: notify when a long-running command finishes in an unfocused session
+  commandNotifications: CommandNotificationConfig;
+  // Read by the backend: regexes matched against session output, with an action each
+  triggers: TriggerConfig[];
 }
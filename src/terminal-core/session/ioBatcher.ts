@@ -39,3 +39,33 @@ export class TerminalOutputBatcher {
     this.write(merged);
   }
 }
+
+// Collects the byte counts of rendered output and acks them in batches, so the
+// backend keeps reading the PTY without an IPC call per write.
+export class TerminalOutputAcker {
+  private pending = 0;
+  private scheduled = false;
+  private readonly ackThreshold = 64 * 1024;
+
+  constructor(private readonly send: (bytes: number) => void) {}
+
+  rendered(bytes: number) {
+    this.pending += bytes;
+    if (this.pending >= this.ackThreshold) {
+      this.flush();
+      return;
+    }
+    if (!this.scheduled) {
+      this.scheduled = true;
+      setTimeout(() => this.flush(), 0);
+    }
+  }
+
+  flush() {
+    this.scheduled = false;
+    if (this.pending === 0) return;
+    const bytes = this.pending;
+    this.pending = 0;
+    this.send(bytes);
+  }
+}
@@ -8,7 +8,7 @@ import { Unicode11Addon } from "@xterm/addon-unicode11";
 import { SerializeAddon } from "@xterm/addon-serialize";
 import type { ImageAddon } from "@xterm/addon-image";
 import type { LigaturesAddon } from "@xterm/addon-ligatures";
-import { invoke } from "@tauri-apps/api/core";
+import { Channel, invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 import { open } from "@tauri-apps/plugin-shell";
 import { useTerminalSuggestStore } from "../stores/terminalSuggestStore";
@@ -17,7 +17,7 @@ import { sendNotification } from "../utils/notifications";
 import { getThemeService } from "../services/themeService";
 import { useTerminalConfigStore } from "../stores/terminalConfigStore";
 import type { TerminalConfig } from "./config/schema";
-import { TerminalOutputBatcher } from "./session/ioBatcher";
+import { TerminalOutputAcker, TerminalOutputBatcher } from "./session/ioBatcher";
 
 const getSessionStore = () => import("../stores/sessionStore").then(m => m.useSessionStore.getState());
 const getEditorStore = () => import("../stores/editorStore").then(m => m.useEditorStore.getState());
@@ -25,6 +25,12 @@ const getEditorStore = () => import("../stores/editorStore").then(m => m.useEdit
 const ACTIVITY_NOTIFICATION_COOLDOWN = 5000;
 const SUGGEST_DEBOUNCE_MS = 150;
 const SUGGEST_LIMIT = 40;
+// Trigger matches are looked up this far from the end of the buffer
+const TRIGGER_SEARCH_ROWS = 500;
+// The highlight can arrive before the output it matched has been written
+const TRIGGER_RETRY_DELAY_MS = 50;
+const TRIGGER_RETRIES = 10;
+const TRIGGER_DEFAULT_COLOR = "#b58900";
 
 const isWebgl2Supported = (() => {
   let isSupported: boolean | undefined;
@@ -66,17 +72,54 @@ export interface TerminalEntry {
   searchResultsDisposable?: IDisposable;
   unlisten?: () => void;
   unlistenPromise?: Promise<() => void>;
+  handleOutput?: (data: Uint8Array) => void;
   lastActivityNotificationAt: number;
   isDisposed: boolean;
   inputBuffer: string;
   suggestLastPrefix: string;
   suggestFetchTimeout?: number | null;
   outputBatcher: TerminalOutputBatcher;
+  outputAcker: TerminalOutputAcker;
   bellAudio?: HTMLAudioElement | null;
 }
 
 const registry = new Map<string, TerminalEntry>();
 
+const sessionIdDecoder = new TextDecoder();
+let outputSubscription: Promise<void> | null = null;
+
+// The backend sends the output of all sessions as one frame per animation frame:
+// for each session, a u8 id length, the id, a little-endian u32 data length and the data
+function dispatchOutputFrame(buffer: ArrayBuffer) {
+  const view = new DataView(buffer);
+  let offset = 0;
+  while (offset < buffer.byteLength) {
+    const idLength = view.getUint8(offset);
+    offset += 1;
+    const sessionId = sessionIdDecoder.decode(new Uint8Array(buffer, offset, idLength));
+    offset += idLength;
+    const dataLength = view.getUint32(offset, true);
+    offset += 4;
+    const data = new Uint8Array(buffer, offset, dataLength);
+    offset += dataLength;
+    registry.get(sessionId)?.handleOutput?.(data);
+  }
+}
+
+// One channel carries every session's output, until it is subscribed (and for
+// backends without it) output arrives as JSON number arrays on per-session events
+function subscribeOutput(): Promise<void> {
+  if (!outputSubscription) {
+    const channel = new Channel<ArrayBuffer>();
+    channel.onmessage = dispatchOutputFrame;
+    outputSubscription = invoke<void>("subscribe_output", { channel }).catch((error) => {
+      console.error(error);
+      outputSubscription = null;
+    });
+  }
+  return outputSubscription;
+}
+
 function getTerminalConfigSnapshot(): TerminalConfig {
   return useTerminalConfigStore.getState().config;
 }
@@ -337,6 +380,12 @@ function createEntry(sessionId: string): TerminalEntry {
 
   let ligaturesAddon: LigaturesAddon | undefined;
 
+  // The backend stops reading the PTY while too much output is unacked,
+  // so output is only acked once xterm has parsed it
+  const outputAcker = new TerminalOutputAcker((bytes) => {
+    invoke("ack_session_output", { id: sessionId, bytes }).catch(console.error);
+  });
+
   const entry: TerminalEntry = {
     terminal,
     fitAddon,
@@ -355,7 +404,10 @@ function createEntry(sessionId: string): TerminalEntry {
     inputBuffer: "",
     suggestLastPrefix: "",
     suggestFetchTimeout: null,
-    outputBatcher: new TerminalOutputBatcher((data) => terminal.write(data)),
+    outputBatcher: new TerminalOutputBatcher((data) =>
+      terminal.write(data, () => outputAcker.rendered(data.length)),
+    ),
+    outputAcker,
     bellAudio: createBellAudio(config),
   };
 
@@ -553,9 +605,8 @@ function createEntry(sessionId: string): TerminalEntry {
     searchStore.setMatchInfo(current, total);
   });
 
-  entry.unlistenPromise = listen<number[]>(`terminal-output-${sessionId}`, async (event) => {
+  const handleOutput = async (data: Uint8Array) => {
     if (entry.isDisposed) return;
-    const data = new Uint8Array(event.payload);
     entry.outputBatcher.push(data);
 
     const currentState = await getSessionStore();
@@ -573,13 +624,25 @@ function createEntry(sessionId: string): TerminalEntry {
         });
       }
     }
+  };
+
+  entry.handleOutput = (data) => {
+    handleOutput(data);
+  };
+
+  entry.unlistenPromise = listen<number[]>(`terminal-output-${sessionId}`, (event) => {
+    handleOutput(new Uint8Array(event.payload));
   });
 
-  entry.unlistenPromise.then((unlisten) => {
+  entry.unlistenPromise.then(async (unlisten) => {
     entry.unlisten = unlisten;
     if (entry.isDisposed) {
       unlisten();
+      return;
     }
+    await subscribeOutput();
+    // Output emitted before a webview reload will never be acked
+    invoke("reset_session_output_window", { id: sessionId }).catch(console.error);
   });
 
   return entry;
@@ -886,6 +949,83 @@ export function searchPrevious(
   return searchDirection(sessionId, query, "previous", options);
 }
 
+// Match of a highlight trigger, in characters of the logical (unwrapped) output line
+export interface TriggerMatch {
+  lineText: string;
+  start: number;
+  end: number;
+  color?: string | null;
+}
+
+// Cell position of every character of the logical line ending at `end`, as the
+// backend counts them: one per cell, blank cells are spaces, combining marks dropped
+function logicalLine(terminal: Terminal, end: number) {
+  const buffer = terminal.buffer.active;
+  let start = end;
+  while (start > 0 && buffer.getLine(start)?.isWrapped) {
+    start--;
+  }
+
+  let text = "";
+  const cells: { row: number; x: number; width: number }[] = [];
+  for (let row = start; row <= end; row++) {
+    const line = buffer.getLine(row);
+    if (!line) continue;
+    for (let x = 0; x < line.length; x++) {
+      const cell = line.getCell(x);
+      if (!cell || cell.getWidth() === 0) continue;
+      const char = cell.getChars();
+      text += char ? String.fromCodePoint(char.codePointAt(0)!) : " ";
+      cells.push({ row, x, width: cell.getWidth() });
+    }
+  }
+  return { start, text, cells };
+}
+
+function decorateTriggerMatch(entry: TerminalEntry, match: TriggerMatch): boolean {
+  const { terminal } = entry;
+  const buffer = terminal.buffer.active;
+  const wanted = match.lineText.trimEnd();
+  const first = Math.max(0, buffer.length - TRIGGER_SEARCH_ROWS);
+
+  // Search upwards so the most recent copy of the line wins
+  for (let end = buffer.length - 1; end >= first; end--) {
+    if (buffer.getLine(end + 1)?.isWrapped) continue;
+    const line = logicalLine(terminal, end);
+    end = line.start;
+    if (line.text.trimEnd() !== wanted) continue;
+
+    const cells = line.cells.slice(match.start, match.end);
+    if (cells.length === 0) return true;
+
+    // One decoration per row the match covers
+    const cursorRow = buffer.baseY + buffer.cursorY;
+    const backgroundColor = match.color || TRIGGER_DEFAULT_COLOR;
+    for (const row of new Set(cells.map((cell) => cell.row))) {
+      const rowCells = cells.filter((cell) => cell.row === row);
+      const last = rowCells[rowCells.length - 1];
+      const marker = terminal.registerMarker(row - cursorRow);
+      if (!marker) continue;
+      const decoration = terminal.registerDecoration({
+        marker,
+        x: rowCells[0].x,
+        width: last.x + last.width - rowCells[0].x,
+        backgroundColor,
+      });
+      decoration?.onDispose(() => marker.dispose());
+    }
+    return true;
+  }
+  return false;
+}
+
+export function highlightTriggerMatch(sessionId: string, match: TriggerMatch, attempt = 0): void {
+  const entry = registry.get(sessionId);
+  if (!entry || entry.isDisposed) return;
+  if (decorateTriggerMatch(entry, match) || attempt >= TRIGGER_RETRIES) return;
+  setTimeout(() => highlightTriggerMatch(sessionId, match, attempt + 1), TRIGGER_RETRY_DELAY_MS);
+}
+
 export function clearSearch(sessionId: string): void {
   const entry = registry.get(sessionId);
   if (!entry || entry.isDisposed) return;
@@ -906,6 +1046,14 @@ export function serializeTerminal(sessionId: string): string | null {
   }
 }
 
+// Saved output of a restored session, written before the new shell's output arrives
+export function writeTerminalHistory(sessionId: string, data: Uint8Array): void {
+  const entry = getOrCreateTerminal(sessionId);
+  if (data.length > 0) {
+    entry.terminal.write(data);
+  }
+}
+
 export function restoreTerminal(sessionId: string, data: string): boolean {
   const entry = registry.get(sessionId);
   if (!entry || entry.isDisposed || !data) {
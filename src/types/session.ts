@@ -36,6 +36,28 @@ export interface SessionInfo {
   customName?: string;     // User-set custom name via double-click rename
   notifyWhenDone?: boolean;    // Notify when command completes
   notifyOnActivity?: boolean;  // Notify on new terminal output
+  markedBy?: string;           // Output that matched a mark-tab trigger, cleared when activated
+}
+
+// Sessions saved when the app last quit, returned by get_restorable_sessions
+export interface PersistedSession {
+  id: string;
+  shell: string;
+  kind: "shell" | "command";
+  args: string[];
+  cwd: string | null;
+  title: string | null;
+}
+
+export interface SessionManifest {
+  version: number;
+  savedAt: number;
+  sessions: PersistedSession[];
+}
+
+export interface RestoredSession {
+  id: string;
+  history: number[];  // Saved output to write before the new shell's output
 }
 
 // Sidebar item types for unified rendering